@@ -2,7 +2,7 @@ use near_sdk::Timestamp;
 
 use crate::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
 #[serde(crate="near_sdk::serde")]
 pub struct Account {
     pub stake_balance: Balance,
@@ -11,16 +11,96 @@ pub struct Account {
     pub unstake_balance: Balance,
     pub unstake_start_timestamp: Timestamp,
     pub unstake_available_epoch: EpochHeight, 
+    pub referrer_id: Option<AccountId>, // người giới thiệu, chỉ set 1 lần
+    pub referral_reward: Balance, // reward nhận được từ các account mình giới thiệu, harvest cùng reward
+    pub loyalty_start_block: BlockHeight, // reward block bắt đầu stake liên tục, reset khi unstake, bình quân theo stake khi nạp thêm
+    pub vesting: VestingSchedule, // reward đã harvest khi pool bật vesting
+    pub penalty_per_share_paid: u128, // penalty_per_share của pool ở lần chốt reward gần nhất
+    pub loyalty_reserve_per_share_paid: u128, // loyalty_reserve_per_share của pool ở lần chốt reward gần nhất
+    pub last_harvest_block: Option<BlockHeight>, // block của lần harvest gần nhất, dùng cho cooldown
+    pub withdraw_scheduled: bool, // keeper được rút unstake balance về account khi hết unbonding
+    pub pending_transfer_count: u32, // số transfer đang chờ callback ghi lại vào account, khác 0 thì không được unregister
 }
 
+impl Account {
+    pub fn new(reward_block: BlockHeight) -> Self {
+        Account {
+            stake_balance: 0,
+            pre_reward: 0,
+            last_block_balance_change: reward_block,
+            unstake_balance: 0,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch: 0,
+            referrer_id: None,
+            referral_reward: 0,
+            loyalty_start_block: reward_block,
+            vesting: VestingSchedule::default(),
+            penalty_per_share_paid: 0,
+            loyalty_reserve_per_share_paid: 0,
+            last_harvest_block: None,
+            withdraw_scheduled: false,
+            pending_transfer_count: 0,
+        }
+    }
+
+    // Block bắt đầu loyalty sau khi nạp thêm amount ở reward_block, bình quân theo stake và làm tròn lên
+    // để phần nạp thêm không được hưởng thời gian stake của phần cũ
+    pub fn weighted_loyalty_start_block(&self, amount: Balance, reward_block: BlockHeight) -> BlockHeight {
+        if self.stake_balance == 0 {
+            return reward_block;
+        }
+
+        let total_stake: U256 = U256::from(self.stake_balance) + U256::from(amount);
+        let weighted_block: U256 = U256::from(self.stake_balance) * U256::from(self.loyalty_start_block) + U256::from(amount) * U256::from(reward_block);
+
+        ((weighted_block + total_stake - 1) / total_stake).as_u64()
+    }
+}
+
+// Layout account trước khi có versioning
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountV1 {
+    pub stake_balance: Balance,
+    pub pre_reward: Balance,
+    pub last_block_balance_change: BlockHeight,
+    pub unstake_balance: Balance,
+    pub unstake_start_timestamp: Timestamp,
+    pub unstake_available_epoch: EpochHeight,
+}
+
+// Account cũ được chuyển sang Current khi đọc và lưu lại ở lần ghi tiếp theo
 #[derive(BorshDeserialize, BorshSerialize)]
 pub enum UpgradableAccount {
-    Current(Account)
+    V1(AccountV1),
+    Current(Account),
+}
+
+impl From<AccountV1> for Account {
+    fn from(account: AccountV1) -> Self {
+        Account {
+            stake_balance: account.stake_balance,
+            pre_reward: account.pre_reward,
+            last_block_balance_change: account.last_block_balance_change,
+            unstake_balance: account.unstake_balance,
+            unstake_start_timestamp: account.unstake_start_timestamp,
+            unstake_available_epoch: account.unstake_available_epoch,
+            referrer_id: None,
+            referral_reward: 0,
+            loyalty_start_block: account.last_block_balance_change,
+            vesting: VestingSchedule::default(),
+            penalty_per_share_paid: 0,
+            loyalty_reserve_per_share_paid: 0,
+            last_harvest_block: None,
+            withdraw_scheduled: false,
+            pending_transfer_count: 0,
+        }
+    }
 }
 
 impl From<UpgradableAccount> for Account {
     fn from(upgradable_account: UpgradableAccount) -> Self {
         match upgradable_account {
+            UpgradableAccount::V1(account) => Account::from(account),
             UpgradableAccount::Current(account) => account,
         }
     }
@@ -35,24 +115,36 @@ impl From<Account> for UpgradableAccount {
 #[derive(Serialize, Deserialize)]
 #[serde(crate="near_sdk::serde")]
 pub struct AccountJson {
+    pub pool_id: PoolId,
     pub account_id: AccountId,
     pub stake_balance: U128,
     pub unstake_balance: U128,
     pub reward: U128,
+    pub referrer_id: Option<AccountId>,
+    pub referral_reward: U128,
+    pub loyalty_multiplier_bps: u32,
+    pub next_harvest_block: BlockHeight, // block sớm nhất được harvest tiếp
     pub can_withdraw: bool,
+    pub withdraw_scheduled: bool,
     pub unstake_start_timestamp: Timestamp,
     pub unstake_available_epoch: EpochHeight,
     pub current_epoch: EpochHeight
 }
 
 impl AccountJson {
-    pub fn from(account_id: AccountId, new_reward: Balance, account: Account) -> Self {
+    pub fn from(pool_id: PoolId, account_id: AccountId, pool: &Pool, new_reward: Balance, account: Account) -> Self {
         AccountJson {
+            pool_id,
             account_id,
             stake_balance: U128(account.stake_balance),
             unstake_balance: U128(account.unstake_balance),
             reward: U128(account.pre_reward + new_reward),
+            referrer_id: account.referrer_id,
+            referral_reward: U128(account.referral_reward),
+            loyalty_multiplier_bps: pool.loyalty.multiplier_bps(pool.reward_block() - account.loyalty_start_block),
+            next_harvest_block: pool.harvest_limits.next_harvest_block(account.last_harvest_block),
             can_withdraw: account.unstake_available_epoch <= env::epoch_height(),
+            withdraw_scheduled: account.withdraw_scheduled,
             unstake_start_timestamp: account.unstake_start_timestamp,
             unstake_available_epoch: account.unstake_available_epoch,
             current_epoch: env::epoch_height(),
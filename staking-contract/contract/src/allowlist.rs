@@ -0,0 +1,112 @@
+use near_sdk::json_types::Base64VecU8;
+
+use crate::*;
+
+const MERKLE_ROOT_LENGTH: usize = 32;
+
+// Proof account nằm trong merkle allowlist.
+// Leaf = sha256("account_id") nếu không có cap, sha256("account_id:cap") nếu có cap,
+// node cha = sha256 của 2 node con đã sắp xếp tăng dần
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AllowlistProof {
+    pub proof: Vec<Base64VecU8>,
+    pub cap: Option<U128>, // stake tối đa của account
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    pub fn set_allowlist_enabled(&mut self, enabled: bool) {
+        self.assert_owner();
+
+        self.allowlist_enabled = enabled;
+    }
+
+    pub fn add_to_allowlist(&mut self, account_ids: Vec<AccountId>) {
+        self.assert_owner();
+
+        for account_id in account_ids.iter() {
+            self.allowlist.insert(account_id);
+        }
+    }
+
+    // account đã stake vẫn unstake / withdraw bình thường, chỉ không stake thêm được
+    pub fn remove_from_allowlist(&mut self, account_ids: Vec<AccountId>) {
+        self.assert_owner();
+
+        for account_id in account_ids.iter() {
+            self.allowlist.remove(account_id);
+        }
+    }
+
+    // Danh sách lớn thì chỉ lưu merkle root, account gửi proof khi stake
+    pub fn set_merkle_root(&mut self, merkle_root: Option<Base64VecU8>) {
+        self.assert_owner();
+
+        if let Some(merkle_root) = &merkle_root {
+            assert_eq!(merkle_root.0.len(), MERKLE_ROOT_LENGTH, "ERR_INVALID_MERKLE_ROOT");
+        }
+        self.merkle_root = merkle_root.map(|merkle_root| merkle_root.0);
+    }
+
+    pub fn get_merkle_root(&self) -> Option<Base64VecU8> {
+        self.merkle_root.clone().map(Base64VecU8)
+    }
+
+    pub fn is_allowlisted(&self, account_id: AccountId) -> bool {
+        !self.allowlist_enabled || self.allowlist.contains(&account_id)
+    }
+
+    pub fn is_allowlist_enabled(&self) -> bool {
+        self.allowlist_enabled
+    }
+
+    pub fn get_allowlist(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountId> {
+        let allowlist = self.allowlist.as_vector();
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        (start as u64..allowlist.len())
+            .take(limit.unwrap_or(50) as usize)
+            .map(|index| allowlist.get(index).unwrap())
+            .collect()
+    }
+}
+
+impl StakingContract {
+
+    // Kiểm tra account được stake, trả về cap trong merkle proof nếu có
+    pub(crate) fn internal_check_allowlist(&self, account_id: &AccountId, proof: Option<&AllowlistProof>) -> Option<Balance> {
+        if let Some(proof) = proof {
+            let merkle_root: &Vec<u8> = self.merkle_root.as_ref().expect("ERR_MERKLE_ROOT_NOT_SET");
+            assert!(verify_merkle_proof(merkle_root, account_id, proof), "ERR_INVALID_MERKLE_PROOF");
+
+            return proof.cap.map(|cap| cap.0);
+        }
+
+        self.assert_allowlisted(account_id);
+        None
+    }
+}
+
+pub(crate) fn merkle_leaf(account_id: &AccountId, cap: Option<U128>) -> Vec<u8> {
+    match cap {
+        Some(cap) => env::sha256(format!("{}:{}", account_id, cap.0).as_bytes()),
+        None => env::sha256(account_id.as_bytes()),
+    }
+}
+
+pub(crate) fn merkle_parent(left: &[u8], right: &[u8]) -> Vec<u8> {
+    if left <= right {
+        env::sha256(&[left, right].concat())
+    } else {
+        env::sha256(&[right, left].concat())
+    }
+}
+
+fn verify_merkle_proof(merkle_root: &[u8], account_id: &AccountId, proof: &AllowlistProof) -> bool {
+    let node: Vec<u8> = proof.proof.iter()
+        .fold(merkle_leaf(account_id, proof.cap), |node, sibling| merkle_parent(&node, &sibling.0));
+
+    node == merkle_root
+}
@@ -1,6 +1,10 @@
 use crate::*;
 
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy)]
+pub const LOYALTY_BASE_BPS: u32 = 10_000;
+// độ chính xác của loyalty_reserve_per_share
+pub const LOYALTY_RESERVE_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug)]
 #[serde(crate = "near_sdk::serde")]
 pub struct ConfigForReward {
     pub reward_numerator: u32,
@@ -11,6 +15,112 @@ impl ConfigForReward {
     
 }
 
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum StakingMode {
+    FungibleToken, // user stake bằng ft token qua ft_transfer_call
+    Native, // user stake bằng NEAR qua deposit_and_stake, reward vẫn trả bằng ft token
+}
+
+// Giới hạn khi stake vào pool
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeLimits {
+    pub min_stake_amount: U128, // số token tối thiểu mỗi lần stake
+    pub deposit_fee_bps: u32, // phí stake (basis point) chuyển vào treasury, chỉ dùng khi stake bằng ft token
+    pub max_total_stake: Option<U128>, // tổng stake tối đa của pool, phần vượt quá được trả lại
+    pub max_account_stake: Option<U128>, // stake tối đa của 1 account trong pool, phần vượt quá được trả lại
+}
+
+// Giới hạn harvest để tránh gọi callback và ft_transfer cho các khoản reward quá nhỏ
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct HarvestLimits {
+    pub min_harvest_amount: U128, // reward tối thiểu (sau phí) mỗi lần harvest
+    pub cooldown_blocks: BlockHeight, // số block tối thiểu giữa 2 lần harvest của 1 account
+}
+
+impl HarvestLimits {
+    pub fn next_harvest_block(&self, last_harvest_block: Option<BlockHeight>) -> BlockHeight {
+        last_harvest_block.map(|block| block + self.cooldown_blocks).unwrap_or(0)
+    }
+}
+
+impl Default for HarvestLimits {
+    fn default() -> Self {
+        Self { min_harvest_amount: U128(0), cooldown_blocks: 0 }
+    }
+}
+
+// Reward được nhân thêm theo thời gian account stake liên tục (reset khi unstake),
+// tăng tuyến tính từ 1x lên max_multiplier_bps sau blocks_to_max block
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LoyaltyConfig {
+    pub max_multiplier_bps: u32, // 10000 = 1x, không có loyalty
+    pub blocks_to_max: BlockHeight,
+}
+
+impl LoyaltyConfig {
+    pub fn multiplier_bps(&self, duration: BlockHeight) -> u32 {
+        if self.blocks_to_max == 0 {
+            return self.max_multiplier_bps;
+        }
+
+        let extra_bps: u128 = (self.max_multiplier_bps - LOYALTY_BASE_BPS) as u128 * duration.min(self.blocks_to_max) as u128 / self.blocks_to_max as u128;
+        LOYALTY_BASE_BPS + extra_bps as u32
+    }
+
+    // Số block reward được cộng thêm khi stake liên tục từ block thứ from_duration đến to_duration,
+    // là diện tích hình thang dưới đường multiplier trừ đi phần 1x
+    pub fn bonus_blocks(&self, from_duration: BlockHeight, to_duration: BlockHeight) -> BlockHeight {
+        if self.max_multiplier_bps == LOYALTY_BASE_BPS || to_duration <= from_duration {
+            return 0;
+        }
+        if self.blocks_to_max == 0 {
+            let extra_bps: u128 = (self.max_multiplier_bps - LOYALTY_BASE_BPS) as u128;
+            return (extra_bps * (to_duration - from_duration) as u128 / LOYALTY_BASE_BPS as u128) as BlockHeight;
+        }
+
+        // 2 lần tích phân của min(duration, blocks_to_max) từ 0 đến duration
+        let blocks_to_max: u128 = self.blocks_to_max as u128;
+        let double_area = |duration: BlockHeight| -> u128 {
+            let duration: u128 = duration as u128;
+            if duration <= blocks_to_max {
+                duration * duration
+            } else {
+                blocks_to_max * blocks_to_max + 2 * blocks_to_max * (duration - blocks_to_max)
+            }
+        };
+
+        let extra_bps: u128 = (self.max_multiplier_bps - LOYALTY_BASE_BPS) as u128;
+        let bonus: u128 = extra_bps * (double_area(to_duration) - double_area(from_duration)) / (2 * blocks_to_max * LOYALTY_BASE_BPS as u128);
+
+        bonus as BlockHeight
+    }
+}
+
+// Harvest được vest tuyến tính trong duration_blocks, chưa qua cliff_blocks thì chưa mở khoá phần nào
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Default, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingConfig {
+    pub cliff_blocks: BlockHeight,
+    pub duration_blocks: BlockHeight,
+    pub early_claim_penalty_bps: u32, // phí trên phần còn khoá khi claim sớm, chia cho các staker của pool
+}
+
+impl Default for LoyaltyConfig {
+    fn default() -> Self {
+        Self { max_multiplier_bps: LOYALTY_BASE_BPS, blocks_to_max: 0 }
+    }
+}
+
+impl Default for StakeLimits {
+    fn default() -> Self {
+        Self { min_stake_amount: U128(0), deposit_fee_bps: 0, max_total_stake: None, max_account_stake: None }
+    }
+}
+
 impl  Default for ConfigForReward {
     fn default() -> Self {
         // APR 15% - 18%
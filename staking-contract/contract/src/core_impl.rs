@@ -1,4 +1,4 @@
-use near_sdk::{Gas, env, PromiseOrValue, ext_contract, PromiseResult};
+use near_sdk::{Gas, env, PromiseOrValue, ext_contract, PromiseResult, serde_json};
 
 use crate::*;
 
@@ -6,7 +6,9 @@ pub const DEPOSIT_ONE_YOCTOR: Balance = 1;
 pub const NO_DEPOSIT: Balance = 0;
 pub const FT_TRANSFER_GAS: Gas = Gas(10_000_000_000_000);
 pub const WITHDRAW_CALLBACK_GAS: Gas = Gas(10_000_000_000_000);
-pub const HARVEST_CALLBACK_GAS: Gas = Gas(10_000_000_000_00);
+pub const HARVEST_CALLBACK_GAS: Gas = Gas(20_000_000_000_000);
+pub const FT_TRANSFER_CALL_GAS: Gas = Gas(50_000_000_000_000);
+pub const UNREGISTER_CALLBACK_GAS: Gas = Gas(20_000_000_000_000);
 
 pub trait FungibleTokenReceiver {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
@@ -15,17 +17,64 @@ pub trait FungibleTokenReceiver {
 #[ext_contract(ext_ft_contract)]
 pub trait FungibleToken {
     fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+    fn ft_transfer_call(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>, msg: String) -> U128;
+    fn ft_balance_of(&self, account_id: AccountId) -> U128;
 }
 
 #[ext_contract(ext_self)]
 pub trait ExtStakingContract {
-    fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId);
-    fn ft_withdraw_callback(&mut self, account_id: AccountId, old_account: Account);
+    fn ft_transfer_callback(&mut self, pool_id: PoolId, amount: U128, account_id: AccountId, fee: U128, referral_reward: U128);
+    fn ft_transfer_call_callback(&mut self, pool_id: PoolId, amount: U128, account_id: AccountId, fee: U128, referral_reward: U128);
+    fn ft_withdraw_callback(&mut self, pool_id: PoolId, account_id: AccountId, old_account: Account);
+    fn emergency_withdraw_callback(&mut self, pool_id: PoolId, account_id: AccountId, old_account: Account);
+    fn solvency_report_callback(&self);
+    fn claim_treasury_callback(&mut self, account_id: AccountId, amount: U128);
+    fn rescue_tokens_callback(&mut self, token_id: AccountId, amount: U128, receiver_id: AccountId);
+    fn claim_vested_callback(&mut self, pool_id: PoolId, account_id: AccountId, amount: U128);
+    fn batch_callback(&self, results: Vec<BatchResultJson>);
+    fn storage_unregister_callback(&mut self, account_id: AccountId, settled_accounts: Vec<(PoolId, Account)>, storage_deposit: U128);
 }
 
+// msg của ft_transfer_call, để trống sẽ stake vào pool 0, fund_reward = true thì nạp token vào quỹ reward của pool
+#[derive(Deserialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StakeMsg {
+    #[serde(default)]
+    pub pool_id: PoolId,
+    #[serde(default)]
+    pub fund_reward: bool,
+    #[serde(default)]
+    pub proof: Option<AllowlistProof>, // merkle proof khi bật allowlist
+    #[serde(default)]
+    pub referrer_id: Option<AccountId>, // người giới thiệu, chỉ lưu ở lần đầu
+}
+
+impl StakeMsg {
+    pub fn parse(msg: &str) -> Self {
+        if msg.is_empty() {
+            StakeMsg::default()
+        } else {
+            serde_json::from_str(msg).expect("ERR_INVALID_MSG")
+        }
+    }
+}
+
+#[near_bindgen]
 impl FungibleTokenReceiver for StakingContract {
     fn ft_on_transfer(&mut self, sender_id: AccountId, amount:U128, msg: String) -> PromiseOrValue<U128> {
-        self.internal_deposit_and_stake(sender_id, amount.0);
+        assert_eq!(self.ft_contract_id, env::predecessor_account_id(), "ERR_INVALID_FT_CONTRACT_ID");
+
+        let stake_msg: StakeMsg = StakeMsg::parse(&msg);
+        if stake_msg.fund_reward {
+            // reward luôn trả bằng ft token nên pool stake bằng NEAR vẫn nạp quỹ reward qua đây
+            assert!(self.internal_has_role(&sender_id, Role::RewardFunder), "ERR_MISSING_ROLE");
+            self.internal_fund_reward(stake_msg.pool_id, sender_id, amount.0);
+        } else {
+            assert_eq!(self.staking_mode, StakingMode::FungibleToken, "ERR_INVALID_STAKING_MODE");
+            // pool đã đầy thì trả lại phần vượt quá cho sender
+            let refund: Balance = self.internal_deposit_and_stake(stake_msg.pool_id, sender_id, amount.0, stake_msg.proof.as_ref(), stake_msg.referrer_id);
+            return PromiseOrValue::Value(U128(refund));
+        }
 
         PromiseOrValue::Value(U128(0))
     }
@@ -35,101 +84,430 @@ impl FungibleTokenReceiver for StakingContract {
 impl StakingContract {
 
     #[payable]
-    pub fn unstake(&mut self, amount: U128) {
+    pub fn deposit_and_stake(&mut self, pool_id: PoolId, proof: Option<AllowlistProof>, referrer_id: Option<AccountId>) {
+        assert_eq!(self.staking_mode, StakingMode::Native, "ERR_INVALID_STAKING_MODE");
+        let amount: Balance = env::attached_deposit();
+        assert!(amount > 0, "ERR_AMOUNT_EQUAL_ZERO");
+
+        let account_id: AccountId = env::predecessor_account_id();
+        let refund: Balance = self.internal_deposit_and_stake(pool_id, account_id.clone(), amount, proof.as_ref(), referrer_id);
+        if refund > 0 {
+            Promise::new(account_id).transfer(refund);
+        }
+    }
+
+    // account_id để trống là account gọi, operator được approve quyền Unstake được unstake thay
+    #[payable]
+    pub fn unstake(&mut self, pool_id: PoolId, amount: U128, account_id: Option<AccountId>) {
+        assert_one_yocto();
+        let account_id: AccountId = self.internal_resolve_account_id(account_id, OperatorPermission::Unstake);
+
+        self.internal_unstake(pool_id, account_id, amount.0);
+    }
+
+    #[payable]
+    pub fn withdraw(&mut self, pool_id: PoolId) -> Promise {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
+        let old_account: Account = self.internal_withdraw(pool_id, account_id.clone());
 
-        self.internal_unstake(account_id, amount.0);
+        self.internal_withdraw_transfer(pool_id, account_id, old_account)
     }
 
+    // Chỉ dùng khi pool bị pause: rút ngay stake + unstake balance, không chờ unbonding và bỏ phần reward chưa trả
     #[payable]
-    pub fn withdraw(&mut self) -> Promise {
+    pub fn emergency_withdraw(&mut self, pool_id: PoolId) -> Promise {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
-        let old_account: Account = self.internal_withdraw(account_id.clone());
-
-        // handle transfer withdraw
-        ext_ft_contract::ext(self.ft_contract_id.clone())
-            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
-            .with_static_gas(FT_TRANSFER_GAS)
-            .ft_transfer(
-                account_id.clone(), 
-                U128(old_account.unstake_balance), 
-                Some(String::from("Staking contract withdraw"))
-            ).then(
-                ext_self::ext(env::current_account_id())
-                .with_attached_deposit(NO_DEPOSIT)
-                .with_static_gas(WITHDRAW_CALLBACK_GAS)
-                .ft_withdraw_callback(
-                    account_id.clone(), 
-                    old_account
-                )
+        let old_account: Account = self.internal_emergency_withdraw(pool_id, account_id.clone());
+        let amount: Balance = old_account.stake_balance + old_account.unstake_balance;
+
+        let transfer_promise: Promise = match self.staking_mode {
+            StakingMode::FungibleToken => ext_ft_contract::ext(self.ft_contract_id.clone())
+                .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+                .with_static_gas(FT_TRANSFER_GAS)
+                .ft_transfer(
+                    account_id.clone(),
+                    U128(amount),
+                    Some(String::from("Staking contract emergency withdraw"))
+                ),
+            StakingMode::Native => Promise::new(account_id.clone()).transfer(amount),
+        };
+
+        transfer_promise.then(
+            ext_self::ext(env::current_account_id())
+            .with_attached_deposit(NO_DEPOSIT)
+            .with_static_gas(WITHDRAW_CALLBACK_GAS)
+            .emergency_withdraw_callback(
+                pool_id,
+                account_id,
+                old_account
             )
+        )
     }
 
+    // Pool bật vesting thì reward được chuyển vào lịch vesting, rút bằng claim_vested.
+    // Operator được approve quyền Harvest gọi thay được nhưng reward vẫn trả về account_id
     #[payable]
-    pub fn harvest(&mut self) -> Promise {
+    pub fn harvest(&mut self, pool_id: PoolId, account_id: Option<AccountId>) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        let account_id: AccountId = self.internal_resolve_account_id(account_id, OperatorPermission::Harvest);
+
+        self.internal_harvest(pool_id, account_id.clone(), account_id, None)
+    }
+
+    // Trả reward cho account khác, không dùng được khi pool bật vesting
+    #[payable]
+    pub fn harvest_to(&mut self, pool_id: PoolId, receiver_id: AccountId) -> PromiseOrValue<U128> {
         assert_one_yocto();
         let account_id: AccountId = env::predecessor_account_id();
-        let upgradable_account = self.accounts.get(&account_id).unwrap();
-        let account = Account::from(upgradable_account);
 
-        let new_reward: Balance = self.internal_calculate_account_reward(&account);
-        let current_reward: Balance = account.pre_reward + new_reward;
+        self.internal_harvest(pool_id, account_id, receiver_id, None)
+    }
 
-        assert!(current_reward > 0, "ERR_REWARD_EQUAL_ZERO");
+    // Trả reward bằng ft_transfer_call để receiver xử lý luôn (vd. nạp vào DEX), phần receiver trả lại được cộng lại vào reward
+    #[payable]
+    pub fn harvest_and_call(&mut self, pool_id: PoolId, receiver_id: AccountId, msg: String) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
 
-        // Cross contract call
-        ext_ft_contract::ext(self.ft_contract_id.clone())
-            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
-            .with_static_gas(FT_TRANSFER_GAS)
-            .ft_transfer(
-                account_id.clone(), 
-                U128(current_reward), 
-                Some("Staking contract harvest".to_string())
-            ).then(
-                ext_self::ext(env::current_account_id())
-                .with_attached_deposit(NO_DEPOSIT)
-                .with_static_gas(HARVEST_CALLBACK_GAS)
-                .ft_transfer_callback(U128(current_reward), account_id.clone())
-            )
+        self.internal_harvest(pool_id, account_id, receiver_id, Some(msg))
+    }
+
+    // Cộng reward vào stake balance thay vì trả token, chỉ dùng được khi stake bằng ft token
+    #[payable]
+    pub fn compound(&mut self, pool_id: PoolId, account_id: Option<AccountId>) -> U128 {
+        assert_one_yocto();
+        let account_id: AccountId = self.internal_resolve_account_id(account_id, OperatorPermission::Compound);
+
+        U128(self.internal_compound(pool_id, account_id))
     }
 
     #[private]
-    pub fn ft_transfer_callback(&mut self, amount: U128, account_id: AccountId) -> U128 {
+    pub fn ft_transfer_callback(&mut self, pool_id: PoolId, amount: U128, account_id: AccountId, fee: U128, referral_reward: U128) -> U128 {
         assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        self.in_flight_outflow -= amount.0 + fee.0;
+        self.internal_finish_transfer(pool_id, &account_id);
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_value) => {
-                let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-                let mut account: Account = Account::from(upgradable_account);
+                self.internal_on_harvest_transfer(pool_id, account_id, amount.0, fee.0, referral_reward.0, amount.0);
 
-                // update account data
-                account.pre_reward = 0;
-                account.last_block_balance_change = env::block_height();
+                amount
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data: trả lại reward đã trừ lúc harvest
+                self.internal_restore_harvest(pool_id, &account_id, amount.0, fee.0, referral_reward.0);
 
-                self.accounts.insert(&account_id, &UpgradableAccount::from(account));
-                self.total_paid_reward_balance += amount.0;
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("ft_transfer_callback"),
+                    account_id,
+                    pool_id: Some(pool_id),
+                    amount,
+                }]).emit();
+                U128(0)
+            },
+        }
+    }
 
-                amount
+    // ft_transfer_call trả về số token receiver đã dùng, phần còn lại đã được ft_resolve_transfer trả về contract
+    #[private]
+    pub fn ft_transfer_call_callback(&mut self, pool_id: PoolId, amount: U128, account_id: AccountId, fee: U128, referral_reward: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        self.in_flight_outflow -= amount.0 + fee.0;
+        self.internal_finish_transfer(pool_id, &account_id);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                let used_amount: Balance = serde_json::from_slice::<U128>(&value)
+                    .map(|used_amount| used_amount.0.min(amount.0))
+                    .unwrap_or(amount.0);
+                self.internal_on_harvest_transfer(pool_id, account_id, amount.0, fee.0, referral_reward.0, used_amount);
+
+                U128(used_amount)
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data: trả lại reward đã trừ lúc harvest
+                self.internal_restore_harvest(pool_id, &account_id, amount.0, fee.0, referral_reward.0);
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("ft_transfer_call_callback"),
+                    account_id,
+                    pool_id: Some(pool_id),
+                    amount,
+                }]).emit();
+                U128(0)
             },
-            PromiseResult::Failed => env::panic_str("ERR_CALL_FAILED"),
         }
     }
 
     #[private]
-    pub fn ft_withdraw_callback(&mut self, account_id: AccountId, old_account: Account) -> U128 {
+    pub fn ft_withdraw_callback(&mut self, pool_id: PoolId, account_id: AccountId, old_account: Account) -> U128 {
         assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        self.in_flight_outflow -= self.internal_ft_principal(old_account.unstake_balance);
+        self.internal_finish_transfer(pool_id, &account_id);
         match env::promise_result(0) {
             PromiseResult::NotReady => unreachable!(),
             PromiseResult::Successful(_value) => {
+                EventLogVariant::Withdraw(vec![AccountLog { pool_id, account_id, amount: U128(old_account.unstake_balance) }]).emit();
                 U128(old_account.unstake_balance)
             },
             PromiseResult::Failed => {
                 // Handle rollback data
-                self.accounts.insert(&account_id, &UpgradableAccount::from(old_account));
+                let amount: U128 = U128(old_account.unstake_balance);
+                self.internal_restore_withdraw(pool_id, &account_id, old_account);
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("ft_withdraw_callback"),
+                    account_id,
+                    pool_id: Some(pool_id),
+                    amount,
+                }]).emit();
                 U128(0)
             },
         }
     }
-}
\ No newline at end of file
+
+    #[private]
+    pub fn emergency_withdraw_callback(&mut self, pool_id: PoolId, account_id: AccountId, old_account: Account) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let amount: U128 = U128(old_account.stake_balance + old_account.unstake_balance);
+        self.in_flight_outflow -= self.internal_ft_principal(amount.0);
+        self.internal_finish_transfer(pool_id, &account_id);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                // reward bị bỏ không còn phải trả, tính như đã trả để không nằm lại trong reward chưa trả của pool
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.total_paid_reward_balance += old_account.pre_reward + old_account.referral_reward;
+                self.pools.replace(pool_id as u64, &pool);
+
+                EventLogVariant::EmergencyWithdraw(vec![AccountLog { pool_id, account_id, amount }]).emit();
+                amount
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data: trả lại stake, unstake và reward đã bỏ
+                self.internal_restore_emergency_withdraw(pool_id, &account_id, old_account);
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("emergency_withdraw_callback"),
+                    account_id,
+                    pool_id: Some(pool_id),
+                    amount,
+                }]).emit();
+                U128(0)
+            },
+        }
+    }
+}
+
+impl StakingContract {
+
+    pub(crate) fn internal_compound(&mut self, pool_id: PoolId, account_id: AccountId) -> Balance {
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        if let Err(err) = self.internal_check_compound(&pool) {
+            panic!("{}", err);
+        }
+
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+        self.internal_settle_account_reward(&mut pool, &mut account);
+        let own_reward: Balance = account.pre_reward;
+        let current_reward: Balance = own_reward + account.referral_reward;
+        let fee: Balance = self.internal_calculate_fee(current_reward);
+        let amount: Balance = current_reward - fee;
+
+        assert!(amount > 0, "ERR_REWARD_EQUAL_ZERO");
+
+        if account.stake_balance == 0 {
+            pool.total_staker += 1;
+            account.loyalty_start_block = pool.reward_block();
+        }
+
+        let referrer_id: Option<AccountId> = account.referrer_id.clone();
+        account.pre_reward = 0;
+        account.referral_reward = 0;
+        account.stake_balance += amount;
+
+        self.internal_save_account(pool_id, &account_id, account);
+
+        pool.pre_reward += self.internal_calculate_global_reward(&pool);
+        pool.total_stake_balance += amount;
+        pool.total_paid_reward_balance += current_reward;
+        pool.last_block_balance_change = pool.reward_block();
+
+        self.pools.replace(pool_id as u64, &pool);
+        self.treasury_balance += fee;
+        self.internal_accrue_referral_reward(pool_id, &account_id, referrer_id, own_reward);
+
+        EventLogVariant::Compound(vec![AccountLog { pool_id, account_id, amount: U128(amount) }]).emit();
+
+        amount
+    }
+
+    // msg = None thì dùng ft_transfer, ngược lại dùng ft_transfer_call tới receiver
+    pub(crate) fn internal_harvest(&mut self, pool_id: PoolId, account_id: AccountId, receiver_id: AccountId, msg: Option<String>) -> PromiseOrValue<U128> {
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+        let (amount, fee): (Balance, Balance) = self.internal_calculate_harvest_amount(&pool, &account);
+
+        if let Err(err) = self.internal_check_harvest(&pool, &account, amount) {
+            panic!("{}", err);
+        }
+        // reward đang vest chỉ claim được về chính account
+        assert!(pool.vesting.is_none() || (receiver_id == account_id && msg.is_none()), "ERR_POOL_VESTING_ENABLED");
+
+        // cooldown tính từ lúc gọi harvest, transfer lỗi cũng không được harvest lại ngay
+        account.last_harvest_block = Some(env::block_height());
+
+        if let Some(vesting) = pool.vesting {
+            self.internal_save_account(pool_id, &account_id, account);
+            return PromiseOrValue::Value(U128(self.internal_vest_reward(pool_id, account_id, vesting)));
+        }
+
+        // trừ reward khỏi account trước khi transfer để không harvest lại được trong lúc chờ callback, transfer lỗi thì trả lại
+        self.internal_settle_account_reward(&mut pool, &mut account);
+        let referral_reward: Balance = account.referral_reward;
+        account.pre_reward = 0;
+        account.referral_reward = 0;
+        account.pending_transfer_count += 1;
+        self.internal_save_account(pool_id, &account_id, account);
+
+        pool.total_paid_reward_balance += amount + fee;
+        self.pools.replace(pool_id as u64, &pool);
+        // phí chỉ cộng vào treasury khi transfer xong nên cũng tính là đang transfer
+        self.in_flight_outflow += amount + fee;
+
+        // Cross contract call
+        let ft_contract = ext_ft_contract::ext(self.ft_contract_id.clone()).with_attached_deposit(DEPOSIT_ONE_YOCTOR);
+        let callback = ext_self::ext(env::current_account_id())
+            .with_attached_deposit(NO_DEPOSIT)
+            .with_static_gas(HARVEST_CALLBACK_GAS);
+
+        match msg {
+            None => ft_contract
+                .with_static_gas(FT_TRANSFER_GAS)
+                .ft_transfer(
+                    receiver_id,
+                    U128(amount),
+                    Some("Staking contract harvest".to_string())
+                ).then(
+                    callback.ft_transfer_callback(pool_id, U128(amount), account_id, U128(fee), U128(referral_reward))
+                ).into(),
+            Some(msg) => ft_contract
+                .with_static_gas(FT_TRANSFER_CALL_GAS)
+                .ft_transfer_call(
+                    receiver_id,
+                    U128(amount),
+                    Some("Staking contract harvest".to_string()),
+                    msg
+                ).then(
+                    callback.ft_transfer_call_callback(pool_id, U128(amount), account_id, U128(fee), U128(referral_reward))
+                ).into(),
+        }
+    }
+
+    // Trả về (số reward account nhận, phí performance giữ lại cho treasury)
+    pub(crate) fn internal_calculate_harvest_amount(&self, pool: &Pool, account: &Account) -> (Balance, Balance) {
+        let new_reward: Balance = self.internal_calculate_account_reward(pool, account);
+        let current_reward: Balance = account.pre_reward + new_reward + account.referral_reward;
+        let fee: Balance = self.internal_calculate_fee(current_reward);
+
+        (current_reward - fee, fee)
+    }
+
+    // Tách khỏi internal_harvest để batch harvest bỏ qua account chưa harvest được thay vì lỗi cả batch
+    pub(crate) fn internal_check_harvest(&self, pool: &Pool, account: &Account, amount: Balance) -> Result<(), &'static str> {
+        if amount == 0 {
+            return Err("ERR_REWARD_EQUAL_ZERO");
+        }
+        if amount < pool.harvest_limits.min_harvest_amount.0 {
+            return Err("ERR_AMOUNT_LESS_THAN_MIN_HARVEST");
+        }
+        if env::block_height() < pool.harvest_limits.next_harvest_block(account.last_harvest_block) {
+            return Err("ERR_HARVEST_COOLDOWN");
+        }
+
+        Ok(())
+    }
+
+    // Điều kiện compound của pool, batch compound trả lỗi cho từng account thay vì lỗi cả batch
+    pub(crate) fn internal_check_compound(&self, pool: &Pool) -> Result<(), &'static str> {
+        if self.staking_mode != StakingMode::FungibleToken {
+            return Err("ERR_INVALID_STAKING_MODE");
+        }
+        if pool.paused {
+            return Err("ERR_CONTRACT_PAUSE");
+        }
+
+        Ok(())
+    }
+
+    // Reward đã trừ lúc harvest, transfer thành công thì cộng phí vào treasury và chia cho người giới thiệu.
+    // used_amount là phần receiver đã dùng, phí và referral reward chỉ tính theo phần này, phần còn lại trả lại account
+    pub(crate) fn internal_on_harvest_transfer(&mut self, pool_id: PoolId, account_id: AccountId, amount: Balance, fee: Balance, referral_reward: Balance, used_amount: Balance) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+
+        let used_fee: Balance = (U256::from(fee) * U256::from(used_amount) / U256::from(amount)).as_u128();
+        let used_referral_reward: Balance = (U256::from(referral_reward) * U256::from(used_amount) / U256::from(amount)).as_u128();
+        let refund_amount: Balance = amount + fee - used_amount - used_fee;
+        let refund_referral_reward: Balance = referral_reward - used_referral_reward;
+
+        // người giới thiệu chỉ nhận theo reward của chính account, không tính reward giới thiệu
+        let own_reward: Balance = used_amount + used_fee - used_referral_reward;
+
+        let referrer_id: Option<AccountId> = account.referrer_id.clone();
+        account.pre_reward += refund_amount - refund_referral_reward;
+        account.referral_reward += refund_referral_reward;
+
+        self.internal_save_account(pool_id, &account_id, account);
+        pool.total_paid_reward_balance -= refund_amount;
+        self.pools.replace(pool_id as u64, &pool);
+        self.treasury_balance += used_fee;
+
+        self.internal_accrue_referral_reward(pool_id, &account_id, referrer_id, own_reward);
+
+        EventLogVariant::Harvest(vec![AccountLog { pool_id, account_id, amount: U128(used_amount) }]).emit();
+    }
+
+    // Trả lại reward và referral reward đã trừ lúc harvest khi transfer lỗi
+    pub(crate) fn internal_restore_harvest(&mut self, pool_id: PoolId, account_id: &AccountId, amount: Balance, fee: Balance, referral_reward: Balance) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, account_id);
+
+        account.pre_reward += amount + fee - referral_reward;
+        account.referral_reward += referral_reward;
+        self.internal_save_account(pool_id, account_id, account);
+
+        pool.total_paid_reward_balance -= amount + fee;
+        self.pools.replace(pool_id as u64, &pool);
+    }
+
+    // Transfer unstake balance đã trừ khỏi account, callback rollback nếu transfer lỗi
+    pub(crate) fn internal_withdraw_transfer(&self, pool_id: PoolId, account_id: AccountId, old_account: Account) -> Promise {
+        let transfer_promise: Promise = match self.staking_mode {
+            StakingMode::FungibleToken => ext_ft_contract::ext(self.ft_contract_id.clone())
+                .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+                .with_static_gas(FT_TRANSFER_GAS)
+                .ft_transfer(
+                    account_id.clone(), 
+                    U128(old_account.unstake_balance), 
+                    Some(String::from("Staking contract withdraw"))
+                ),
+            StakingMode::Native => Promise::new(account_id.clone()).transfer(old_account.unstake_balance),
+        };
+
+        transfer_promise.then(
+            ext_self::ext(env::current_account_id())
+            .with_attached_deposit(NO_DEPOSIT)
+            .with_static_gas(WITHDRAW_CALLBACK_GAS)
+            .ft_withdraw_callback(
+                pool_id,
+                account_id, 
+                old_account
+            )
+        )
+    }
+}
@@ -3,40 +3,88 @@ use crate::*;
 #[derive(Serialize, Deserialize)]
 #[serde(crate="near_sdk::serde")]
 pub struct PoolJson {
+    pub pool_id: PoolId,
+    pub config: ConfigForReward,
     pub total_stake_balance: U128,
+    pub total_unstake_balance: U128,
     pub total_reward: U128,
+    pub total_funded_reward: U128,
     pub total_stakers: U128,
     pub is_paused: bool,
+    pub unbonding_period: EpochHeight,
+    pub limits: StakeLimits,
+    pub referral_bps: u32,
+    pub loyalty: LoyaltyConfig,
+    pub vesting: Option<VestingConfig>,
+    pub harvest_limits: HarvestLimits,
+    pub staking_mode: StakingMode,
 }
 
 #[near_bindgen]
 impl StakingContract {
-    pub fn get_account_info(&self, account_id: AccountId) -> AccountJson {
-        let upgradable_account = self.accounts.get(&account_id).unwrap();
+    pub fn get_account_info(&self, pool_id: PoolId, account_id: AccountId) -> AccountJson {
+        let pool = self.internal_get_pool(pool_id);
 
-        let account = Account::from(upgradable_account);
+        let account = self.internal_get_account(pool_id, &account_id);
 
-        let new_reward = self.internal_calculate_account_reward(&account);
+        let new_reward = self.internal_calculate_account_reward(&pool, &account);
 
-        AccountJson::from(account_id, new_reward, account)
+        AccountJson::from(pool_id, account_id, &pool, new_reward, account)
     }
 
-    pub fn get_account_reward(&self, account_id: AccountId) -> Balance {
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
+    pub fn get_account_reward(&self, pool_id: PoolId, account_id: AccountId) -> Balance {
+        let pool = self.internal_get_pool(pool_id);
 
-        let account = Account::from(upgradable_account);
-    
-        let new_reward = self.internal_calculate_account_reward(&account);
+        let account = self.internal_get_account(pool_id, &account_id);
 
-        account.pre_reward + new_reward
+        let new_reward = self.internal_calculate_account_reward(&pool, &account);
+
+        account.pre_reward + new_reward + account.referral_reward
     }
 
-    pub fn get_pool_info(&self) -> PoolJson {
+    pub fn get_pool_info(&self, pool_id: PoolId) -> PoolJson {
+        let pool = self.internal_get_pool(pool_id);
+
         PoolJson {
-            total_stake_balance: U128(self.total_stake_balance),
-            total_reward: U128(self.pre_reward + self.internal_calculate_global_reward()),
-            total_stakers: U128(self.total_staker),
-            is_paused: self.paused,
+            pool_id,
+            config: pool.config,
+            total_stake_balance: U128(pool.total_stake_balance),
+            total_unstake_balance: U128(pool.total_unstake_balance),
+            total_reward: U128(pool.pre_reward + self.internal_calculate_global_reward(&pool)),
+            total_funded_reward: U128(pool.total_funded_reward_balance),
+            total_stakers: U128(pool.total_staker),
+            is_paused: pool.paused,
+            unbonding_period: pool.unbonding_period,
+            limits: pool.limits,
+            referral_bps: pool.referral_bps,
+            loyalty: pool.loyalty,
+            vesting: pool.vesting,
+            harvest_limits: pool.harvest_limits,
+            staking_mode: self.staking_mode,
         }
     }
-}
\ No newline at end of file
+
+    pub fn get_accounts(&self, pool_id: PoolId, from_index: Option<U128>, limit: Option<u64>) -> Vec<AccountJson> {
+        let pool = self.internal_get_pool(pool_id);
+        let account_ids = pool.account_ids.as_vector();
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        (start as u64..account_ids.len())
+            .take(limit.unwrap_or(50) as usize)
+            .map(|index| self.get_account_info(pool_id, account_ids.get(index).unwrap()))
+            .collect()
+    }
+
+    pub fn get_accounts_count(&self, pool_id: PoolId) -> U128 {
+        U128(self.internal_get_pool(pool_id).account_ids.len() as u128)
+    }
+
+    pub fn get_pools(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<PoolJson> {
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        (start as u64..self.pools.len())
+            .take(limit.unwrap_or(50) as usize)
+            .map(|pool_id| self.get_pool_info(pool_id as PoolId))
+            .collect()
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+use near_sdk::serde_json;
+use near_sdk::json_types::U64;
+
+use crate::*;
+
+pub const EVENT_STANDARD: &str = "staking";
+pub const EVENT_STANDARD_VERSION: &str = "1.0.0";
+
+// Event theo chuẩn NEP-297, log ra dạng EVENT_JSON:{"standard":...,"version":...,"event":...,"data":[...]}
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum EventLogVariant {
+    Register(Vec<StorageLog>),
+    Unregister(Vec<StorageLog>),
+    Stake(Vec<AccountLog>),
+    Unstake(Vec<AccountLog>),
+    Withdraw(Vec<AccountLog>),
+    ScheduleWithdraw(Vec<AccountLog>),
+    CancelScheduledWithdraw(Vec<AccountLog>),
+    Harvest(Vec<AccountLog>),
+    EmergencyWithdraw(Vec<AccountLog>),
+    Compound(Vec<AccountLog>),
+    Vest(Vec<AccountLog>),
+    ClaimVested(Vec<AccountLog>),
+    ReferralReward(Vec<ReferralLog>),
+    ApproveOperator(Vec<ApprovalLog>),
+    ClaimTreasury(Vec<TreasuryLog>),
+    CallbackFailed(Vec<CallbackFailedLog>),
+    Pause(Vec<PoolLog>),
+    Resume(Vec<PoolLog>),
+    CreatePool(Vec<PoolConfigLog>),
+    UpdateConfig(Vec<PoolConfigLog>),
+    FundReward(Vec<AccountLog>),
+    ProposeOwner(Vec<OwnerLog>),
+    AcceptOwner(Vec<OwnerLog>),
+    GrantRole(Vec<RoleLog>),
+    RevokeRole(Vec<RoleLog>),
+    ScheduleAction(Vec<ActionLog>),
+    ExecuteAction(Vec<ActionLog>),
+    CancelAction(Vec<ActionLog>),
+    RescueTokens(Vec<RescueLog>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EventLog {
+    pub standard: String,
+    pub version: String,
+
+    #[serde(flatten)]
+    pub event: EventLogVariant,
+}
+
+impl fmt::Display for EventLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("EVENT_JSON:{}", &serde_json::to_string(self).map_err(|_| fmt::Error)?))
+    }
+}
+
+impl EventLogVariant {
+    pub fn emit(self) {
+        let event_log = EventLog {
+            standard: EVENT_STANDARD.to_string(),
+            version: EVENT_STANDARD_VERSION.to_string(),
+            event: self,
+        };
+
+        env::log_str(&event_log.to_string());
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageLog {
+    pub account_id: AccountId,
+    pub amount: U128, // NEAR deposit cho storage
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountLog {
+    pub pool_id: PoolId,
+    pub account_id: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CallbackFailedLog {
+    pub callback: String, // tên callback bị rollback
+    pub account_id: AccountId,
+    pub pool_id: Option<PoolId>,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolLog {
+    pub pool_id: PoolId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolConfigLog {
+    pub pool_id: PoolId,
+    pub config: ConfigForReward,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OwnerLog {
+    pub owner_id: AccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleLog {
+    pub account_id: AccountId,
+    pub role: Role,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActionLog {
+    pub action_id: U64,
+    pub action: AdminAction,
+    pub eta: U64,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RescueLog {
+    pub token_id: AccountId,
+    pub receiver_id: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryLog {
+    pub account_id: AccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ReferralLog {
+    pub pool_id: PoolId,
+    pub referrer_id: AccountId,
+    pub account_id: AccountId, // account được giới thiệu
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ApprovalLog {
+    pub account_id: AccountId,
+    pub operator_id: AccountId,
+    pub permissions: Vec<OperatorPermission>, // rỗng là đã thu hồi
+}
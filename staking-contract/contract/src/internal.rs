@@ -3,124 +3,510 @@ use crate::*;
 #[near_bindgen]
 impl StakingContract {
 
-    pub(crate) fn internal_unstake(&mut self, account_id: AccountId, amount: Balance) {
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
+    pub(crate) fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_NOT_OWNER");
+    }
+
+    pub(crate) fn assert_role(&self, role: Role) {
+        assert!(self.internal_has_role(&env::predecessor_account_id(), role), "ERR_MISSING_ROLE");
+    }
+
+    pub(crate) fn assert_allowlisted(&self, account_id: &AccountId) {
+        assert!(!self.allowlist_enabled || self.allowlist.contains(account_id), "ERR_NOT_ALLOWLISTED");
+    }
+
+    pub(crate) fn internal_create_pool(&mut self, config: ConfigForReward) -> PoolId {
+        assert!(config.reward_denumerator > 0, "ERR_INVALID_CONFIG");
+        let pool_id = self.pools.len() as PoolId;
+        self.pools.push(&Pool::new(pool_id, config));
+
+        EventLogVariant::CreatePool(vec![PoolConfigLog { pool_id, config }]).emit();
+
+        pool_id
+    }
 
-        let mut account = Account::from(upgradable_account);
+    // config mới chỉ áp dụng từ block hiện tại, phần account chưa chốt trước đó vẫn tính theo config cũ
+    pub(crate) fn internal_update_config(&mut self, pool_id: PoolId, config: ConfigForReward) {
+        assert!(config.reward_denumerator > 0, "ERR_INVALID_CONFIG");
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+
+        self.internal_checkpoint_reward(&mut pool);
+        pool.config = config;
+
+        self.pools.replace(pool_id as u64, &pool);
+
+        EventLogVariant::UpdateConfig(vec![PoolConfigLog { pool_id, config }]).emit();
+    }
+
+    pub(crate) fn internal_update_loyalty(&mut self, pool_id: PoolId, loyalty: LoyaltyConfig) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+
+        self.internal_checkpoint_reward(&mut pool);
+        pool.loyalty = loyalty;
+
+        self.pools.replace(pool_id as u64, &pool);
+    }
+
+    // chốt reward của pool theo config cũ và lưu lại config cũ trước khi đổi
+    pub(crate) fn internal_checkpoint_reward(&self, pool: &mut Pool) {
+        self.internal_accrue_loyalty_reserve(pool);
+        pool.pre_reward += self.internal_calculate_global_reward(pool);
+        pool.last_block_balance_change = pool.reward_block();
+        pool.reward_checkpoints.push(&RewardCheckpoint {
+            end_block: pool.reward_block(),
+            config: pool.config,
+            loyalty: pool.loyalty,
+        });
+
+        // giữ số checkpoint cố định để tính reward không phải duyệt quá MAX_REWARD_CHECKPOINTS đoạn
+        if pool.reward_checkpoints.len() > MAX_REWARD_CHECKPOINTS {
+            let checkpoints: Vec<RewardCheckpoint> = pool.reward_checkpoints.to_vec();
+            pool.reward_checkpoints.replace(0, &checkpoints[0].merge(&checkpoints[1]));
+            for (index, checkpoint) in checkpoints.iter().enumerate().skip(2) {
+                pool.reward_checkpoints.replace(index as u64 - 1, checkpoint);
+            }
+            pool.reward_checkpoints.pop();
+        }
+    }
+
+    pub(crate) fn internal_get_pool(&self, pool_id: PoolId) -> Pool {
+        self.pools.get(pool_id as u64).expect("ERR_POOL_NOT_FOUND")
+    }
+
+    pub(crate) fn internal_get_account(&self, pool_id: PoolId, account_id: &AccountId) -> Account {
+        match self.accounts.get(&(pool_id, account_id.clone())) {
+            Some(upgradable_account) => Account::from(upgradable_account),
+            // account của bản single pool chưa migrate vẫn đọc được ở pool 0
+            None if pool_id == 0 => self.internal_get_old_account(account_id).expect("ERR_ACCOUNT_NOT_FOUND"),
+            None => env::panic_str("ERR_ACCOUNT_NOT_FOUND"),
+        }
+    }
+
+    // Lưu account của pool, phần storage tăng thêm được trừ vào storage deposit của account
+    pub(crate) fn internal_save_account(&mut self, pool_id: PoolId, account_id: &AccountId, account: Account) {
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        self.accounts.insert(&(pool_id, account_id.clone()), &UpgradableAccount::from(account));
+
+        self.internal_update_storage_usage(account_id, initial_storage_usage);
+    }
+
+    pub(crate) fn internal_unstake(&mut self, pool_id: PoolId, account_id: AccountId, amount: Balance) {
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
 
         assert!(amount <= account.stake_balance, "ERR_AMOUNT_MUST_LESS_THAN_BALANCE");
 
         // if exist account, update balance and update pre data
-        let new_reward: Balance = self.internal_calculate_account_reward(&account);
+        self.internal_settle_account_reward(&mut pool, &mut account);
 
         // update account data
-        account.pre_reward += new_reward;
         account.stake_balance -= amount;
-        account.last_block_balance_change = env::block_height();
+        account.loyalty_start_block = pool.reward_block();
         account.unstake_balance += amount;
         account.unstake_start_timestamp = env::block_timestamp();
-        account.unstake_available_epoch = env::epoch_height() + 1;
-        
+        account.unstake_available_epoch = env::epoch_height() + pool.unbonding_period;
+
         if account.stake_balance == 0 {
-            self.total_staker -= 1;
+            pool.total_staker -= 1;
         }
 
         // update new account data
-        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+        self.internal_save_account(pool_id, &account_id, account);
+
+        // update pool data
+        let new_contract_reward: Balance = self.internal_calculate_global_reward(&pool);
+        pool.total_stake_balance -= amount;
+        pool.total_unstake_balance += amount;
+        pool.pre_reward += new_contract_reward;
+        pool.last_block_balance_change = pool.reward_block();
 
-        // update contract data
-        let new_contract_reward: Balance = self.internal_calculate_global_reward();
-        self.total_stake_balance -= amount;
-        self.pre_reward += new_contract_reward;
-        self.last_block_balance_change = env::block_height();
+        self.pools.replace(pool_id as u64, &pool);
+
+        EventLogVariant::Unstake(vec![AccountLog { pool_id, account_id, amount: U128(amount) }]).emit();
     }
 
-    pub(crate) fn internal_withdraw(&mut self, account_id: AccountId) -> Account {
-        let upgradable_account: UpgradableAccount = self.accounts.get(&account_id).unwrap();
-        let account: Account = Account::from(upgradable_account);
+    pub(crate) fn internal_withdraw(&mut self, pool_id: PoolId, account_id: AccountId) -> Account {
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let account: Account = self.internal_get_account(pool_id, &account_id);
 
         assert!(account.unstake_balance > 0, "ERR_UNSTAKE_BALANCE_IS_ZERO");
         assert!(account.unstake_available_epoch <= env::epoch_height(), "ERR_DISABLE_WITHDRAW");
 
-        let new_account: Account = Account {
-            pre_reward: account.pre_reward,
-            stake_balance: account.stake_balance,
-            last_block_balance_change: account.last_block_balance_change,
-            unstake_balance: 0,
-            unstake_start_timestamp: 0,
-            unstake_available_epoch: 0
-        };
+        let mut new_account: Account = account.clone();
+        new_account.unstake_balance = 0;
+        new_account.unstake_start_timestamp = 0;
+        new_account.unstake_available_epoch = 0;
+        new_account.withdraw_scheduled = false;
+        new_account.pending_transfer_count += 1;
+
+        self.internal_save_account(pool_id, &account_id, new_account);
 
-        self.accounts.insert(&account_id, &UpgradableAccount::from(new_account));
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        pool.total_unstake_balance -= account.unstake_balance;
+        self.pools.replace(pool_id as u64, &pool);
+        self.in_flight_outflow += self.internal_ft_principal(account.unstake_balance);
 
         account
     }
 
-    pub(crate) fn internal_deposit_and_stake(&mut self, account_id: AccountId, amount:u128) {
+    // Gọi đầu callback của transfer đã tăng pending_transfer_count
+    pub(crate) fn internal_finish_transfer(&mut self, pool_id: PoolId, account_id: &AccountId) {
+        let mut account: Account = self.internal_get_account(pool_id, account_id);
+        account.pending_transfer_count -= 1;
+        self.internal_save_account(pool_id, account_id, account);
+    }
+
+    // Chỉ trả lại phần unstake đã rút, stake và reward có thể đã thay đổi trong lúc chờ callback
+    pub(crate) fn internal_restore_withdraw(&mut self, pool_id: PoolId, account_id: &AccountId, old_account: Account) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, account_id);
+
+        account.unstake_balance += old_account.unstake_balance;
+        account.unstake_start_timestamp = account.unstake_start_timestamp.max(old_account.unstake_start_timestamp);
+        account.unstake_available_epoch = account.unstake_available_epoch.max(old_account.unstake_available_epoch);
+        account.withdraw_scheduled |= old_account.withdraw_scheduled;
+        self.internal_save_account(pool_id, account_id, account);
+
+        pool.total_unstake_balance += old_account.unstake_balance;
+        self.pools.replace(pool_id as u64, &pool);
+    }
+
+    // Trả về account trước khi rút (reward đã chốt) để rollback nếu transfer lỗi
+    pub(crate) fn internal_emergency_withdraw(&mut self, pool_id: PoolId, account_id: AccountId) -> Account {
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        assert!(pool.paused, "ERR_POOL_NOT_PAUSED");
+
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+        assert!(account.stake_balance + account.unstake_balance > 0, "ERR_BALANCE_IS_ZERO");
+
+        self.internal_settle_account_reward(&mut pool, &mut account);
+
+        if account.stake_balance > 0 {
+            pool.pre_reward += self.internal_calculate_global_reward(&pool);
+            pool.total_stake_balance -= account.stake_balance;
+            pool.total_staker -= 1;
+            pool.last_block_balance_change = pool.reward_block();
+        }
+        pool.total_unstake_balance -= account.unstake_balance;
+        self.pools.replace(pool_id as u64, &pool);
+        self.in_flight_outflow += self.internal_ft_principal(account.stake_balance + account.unstake_balance);
+
+        let mut new_account: Account = Account::new(pool.reward_block());
+        new_account.referrer_id = account.referrer_id.clone();
+        new_account.vesting = account.vesting.clone();
+        new_account.pending_transfer_count = account.pending_transfer_count + 1;
+        self.internal_save_account(pool_id, &account_id, new_account);
+
+        account
+    }
+
+    pub(crate) fn internal_restore_emergency_withdraw(&mut self, pool_id: PoolId, account_id: &AccountId, old_account: Account) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, account_id);
+
+        self.internal_settle_account_reward(&mut pool, &mut account);
+        account.pre_reward += old_account.pre_reward;
+        account.referral_reward += old_account.referral_reward;
+        account.unstake_balance += old_account.unstake_balance;
+        account.unstake_available_epoch = account.unstake_available_epoch.max(old_account.unstake_available_epoch);
+
+        if old_account.stake_balance > 0 {
+            if account.stake_balance == 0 {
+                pool.total_staker += 1;
+            }
+            account.stake_balance += old_account.stake_balance;
+
+            pool.pre_reward += self.internal_calculate_global_reward(&pool);
+            pool.total_stake_balance += old_account.stake_balance;
+            pool.last_block_balance_change = pool.reward_block();
+        }
+        pool.total_unstake_balance += old_account.unstake_balance;
+        self.pools.replace(pool_id as u64, &pool);
+
+        self.internal_save_account(pool_id, account_id, account);
+    }
+
+    // Trả về số token không được stake do pool đã đạt max_total_stake hoặc account đã đạt max_account_stake
+    pub(crate) fn internal_deposit_and_stake(&mut self, pool_id: PoolId, account_id: AccountId, amount:u128, proof: Option<&AllowlistProof>, referrer_id: Option<AccountId>) -> Balance {
         // Validate data
-        let upgradable_account = self.accounts.get(&account_id);
-        assert!(upgradable_account.is_some(), "ERR_ACCOUNT_NOT_FOUND");
-        assert_eq!(self.paused, false, "ERR_CONTRACT_PAUSE");
-        assert_eq!(self.ft_contract_id, env::predecessor_account_id(), "ERR_INVALID_FT_CONTRACT_ID");
+        self.internal_migrate_on_access(pool_id, &account_id);
+        assert!(self.storage_accounts.contains_key(&account_id), "ERR_ACCOUNT_NOT_FOUND");
+        let proof_cap: Option<Balance> = self.internal_check_allowlist(&account_id, proof);
+        let pool: Pool = self.internal_get_pool(pool_id);
+        assert!(!pool.paused, "ERR_CONTRACT_PAUSE");
+        assert!(amount >= pool.limits.min_stake_amount.0, "ERR_AMOUNT_LESS_THAN_MIN_STAKE");
 
-        let mut account = Account::from(upgradable_account.unwrap());
+        let stake_balance: Balance = self.accounts.get(&(pool_id, account_id.clone()))
+            .map_or(0, |upgradable_account| Account::from(upgradable_account).stake_balance);
+        let pool_available: Balance = pool.limits.max_total_stake
+            .map_or(Balance::MAX, |max_total_stake| max_total_stake.0.saturating_sub(pool.total_stake_balance));
+        // cap trong merkle proof chỉ được thấp hơn cap của pool
+        let max_account_stake: Option<Balance> = match (pool.limits.max_account_stake.map(|max_account_stake| max_account_stake.0), proof_cap) {
+            (Some(pool_cap), Some(proof_cap)) => Some(pool_cap.min(proof_cap)),
+            (pool_cap, proof_cap) => pool_cap.or(proof_cap),
+        };
+        let account_available: Balance = max_account_stake
+            .map_or(Balance::MAX, |max_account_stake| max_account_stake.saturating_sub(stake_balance));
+        let available: Balance = pool_available.min(account_available);
+        let accepted_amount: Balance = amount.min(available);
+        if accepted_amount == 0 {
+            return amount;
+        }
 
-        if account.stake_balance == 0 {
-            self.total_staker += 1;
+        let fee: Balance = accepted_amount * pool.limits.deposit_fee_bps as u128 / MAX_FEE_BPS as u128;
+        let refund: Balance = amount - accepted_amount;
+        let amount: Balance = accepted_amount - fee;
+
+        // lần đầu stake vào pool thì tạo account của pool, storage trừ vào storage deposit
+        if !self.accounts.contains_key(&(pool_id, account_id.clone())) {
+            self.internal_register_account(pool_id, account_id.clone());
         }
 
-        let new_reward = self.internal_calculate_account_reward(&account);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+
+        self.internal_settle_account_reward(&mut pool, &mut account);
+
+        if account.stake_balance == 0 {
+            pool.total_staker += 1;
+        }
 
         // update account data
-        account.pre_reward += new_reward;
+        account.loyalty_start_block = account.weighted_loyalty_start_block(amount, pool.reward_block());
         account.stake_balance += amount;
-        account.last_block_balance_change = env::block_height();
+        if let (None, Some(referrer_id)) = (&account.referrer_id, referrer_id) {
+            assert_ne!(referrer_id, account_id, "ERR_INVALID_REFERRER");
+            assert!(self.storage_accounts.contains_key(&referrer_id), "ERR_REFERRER_NOT_FOUND");
+            account.referrer_id = Some(referrer_id);
+        }
 
-        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+        self.internal_save_account(pool_id, &account_id, account);
 
         // update pool data
-        let new_contract_reward = self.internal_calculate_global_reward();
-        self.total_stake_balance += amount;
-        self.pre_reward += new_contract_reward;
-        self.last_block_balance_change = env::block_height();
-    }
-
-    pub(crate) fn internal_register_account(&mut self, account_id: AccountId) {
-        let account = Account {
-            stake_balance: 0,
-            pre_reward: 0,
-            last_block_balance_change: env::block_height(),
-            unstake_balance: 0,
-            unstake_start_timestamp: 0,
-            unstake_available_epoch: 0
-        };
-    
-        self.accounts.insert(&account_id, &UpgradableAccount::from(account));
+        let new_contract_reward = self.internal_calculate_global_reward(&pool);
+        pool.total_stake_balance += amount;
+        pool.pre_reward += new_contract_reward;
+        pool.last_block_balance_change = pool.reward_block();
+
+        self.pools.replace(pool_id as u64, &pool);
+        self.treasury_balance += fee;
+
+        EventLogVariant::Stake(vec![AccountLog { pool_id, account_id, amount: U128(amount) }]).emit();
+
+        refund
     }
 
-    pub(crate) fn internal_calculate_account_reward(&self, account: &Account) -> Balance {
-        let lasted_block = if self.paused {
-            self.pause_in_block
-        } else {
-            env::block_height()
-        };
+    // Nạp ft token vào quỹ reward của pool, token chỉ dùng để trả reward
+    pub(crate) fn internal_fund_reward(&mut self, pool_id: PoolId, account_id: AccountId, amount: Balance) {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
 
-        let diff_block = lasted_block - account.last_block_balance_change;
-        let reward: Balance = (account.stake_balance * self.config.reward_numerator as u128 * diff_block as u128) / self.config.reward_denumerator as u128;
+        pool.total_funded_reward_balance += amount;
+        self.pools.replace(pool_id as u64, &pool);
 
-        reward
+        EventLogVariant::FundReward(vec![AccountLog { pool_id, account_id, amount: U128(amount) }]).emit();
     }
 
-    pub(crate) fn internal_calculate_global_reward(&self) -> Balance {
-        let lasted_block = if self.paused {
-            self.pause_in_block
-        } else {
-            env::block_height()
+    // Cộng phần trăm reward account đã nhận cho người giới thiệu, bỏ qua nếu người giới thiệu không còn account trong pool
+    pub(crate) fn internal_accrue_referral_reward(&mut self, pool_id: PoolId, account_id: &AccountId, referrer_id: Option<AccountId>, reward: Balance) {
+        let referrer_id: AccountId = match referrer_id {
+            Some(referrer_id) => referrer_id,
+            None => return,
         };
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let referral_reward: Balance = reward * pool.referral_bps as u128 / MAX_FEE_BPS as u128;
+
+        if referral_reward == 0 {
+            return;
+        }
+
+        if let Some(upgradable_account) = self.accounts.get(&(pool_id, referrer_id.clone())) {
+            let mut referrer: Account = Account::from(upgradable_account);
+            referrer.referral_reward += referral_reward;
+            self.internal_save_account(pool_id, &referrer_id, referrer);
+
+            pool.total_referral_reward_balance += referral_reward;
+            self.pools.replace(pool_id as u64, &pool);
+
+            EventLogVariant::ReferralReward(vec![ReferralLog {
+                pool_id,
+                referrer_id,
+                account_id: account_id.clone(),
+                amount: U128(referral_reward),
+            }]).emit();
+        }
+    }
+
+    // Chốt reward và xoá account khỏi pool, trả về account đã chốt reward để thanh toán
+    pub(crate) fn internal_remove_account(&mut self, pool_id: PoolId, account_id: &AccountId) -> Account {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, account_id);
+
+        self.internal_settle_account_reward(&mut pool, &mut account);
+
+        if account.stake_balance > 0 {
+            let new_contract_reward: Balance = self.internal_calculate_global_reward(&pool);
+            pool.total_stake_balance -= account.stake_balance;
+            pool.total_staker -= 1;
+            pool.pre_reward += new_contract_reward;
+            pool.last_block_balance_change = pool.reward_block();
+        }
+
+        pool.total_unstake_balance -= account.unstake_balance;
+        pool.account_ids.remove(account_id);
+        self.pools.replace(pool_id as u64, &pool);
+        self.accounts.remove(&(pool_id, account_id.clone()));
+
+        account
+    }
+
+    pub(crate) fn internal_register_account(&mut self, pool_id: PoolId, account_id: AccountId) {
+        // account của bản single pool cũ chưa migrate thì chuyển sang pool 0 thay vì tạo mới
+        if pool_id == 0 && self.internal_migrate_old_account(&account_id) {
+            return;
+        }
+
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+
+        pool.account_ids.insert(&account_id);
+        self.pools.replace(pool_id as u64, &pool);
+        self.accounts.insert(&(pool_id, account_id.clone()), &UpgradableAccount::from(Account::new(pool.reward_block())));
+
+        self.internal_update_storage_usage(&account_id, initial_storage_usage);
+    }
+
+    // Chốt reward tới block hiện tại vào pre_reward, phần loyalty cộng vào tổng loyalty của pool
+    // và trả lại phần loyalty tối đa đã giữ cho account
+    pub(crate) fn internal_settle_account_reward(&self, pool: &mut Pool, account: &mut Account) {
+        let (reward, loyalty_bonus) = self.internal_calculate_account_reward_with_bonus(pool, account);
+
+        self.internal_accrue_loyalty_reserve(pool);
+        let loyalty_reserve: U256 = U256::from(account.stake_balance) * U256::from(pool.loyalty_reserve_per_share - account.loyalty_reserve_per_share_paid) / U256::from(LOYALTY_RESERVE_PRECISION);
+
+        account.pre_reward += reward + loyalty_bonus + self.internal_calculate_penalty_share(pool, account);
+        account.last_block_balance_change = pool.reward_block();
+        account.penalty_per_share_paid = pool.penalty_per_share;
+        account.loyalty_reserve_per_share_paid = pool.loyalty_reserve_per_share;
+        pool.total_loyalty_bonus_balance += loyalty_bonus;
+        pool.total_loyalty_reserve_balance -= loyalty_reserve.as_u128();
+    }
+
+    // Cộng reward loyalty tối đa mà các staker có thể nhận tới block hiện tại vào pool, tính theo max multiplier
+    pub(crate) fn internal_accrue_loyalty_reserve(&self, pool: &mut Pool) {
+        let (per_share_increase, reserve_increase) = self.internal_calculate_loyalty_reserve(pool);
 
-        let diff_block = lasted_block - self.last_block_balance_change;
-        let reward: Balance = (self.total_stake_balance * self.config.reward_numerator as u128 * diff_block as u128) / self.config.reward_denumerator as u128;
+        pool.loyalty_reserve_per_share += per_share_increase;
+        pool.total_loyalty_reserve_balance += reserve_increase;
+        pool.loyalty_reserve_block = pool.reward_block();
+    }
+
+    // Làm tròn lên để phần giữ cho pool không nhỏ hơn tổng phần trả lại cho các account
+    pub(crate) fn internal_calculate_loyalty_reserve(&self, pool: &Pool) -> (u128, Balance) {
+        let diff_block = pool.reward_block() - pool.loyalty_reserve_block;
+        let numerator: U256 = U256::from(pool.config.reward_numerator) * U256::from(diff_block) * U256::from(pool.loyalty.max_multiplier_bps - LOYALTY_BASE_BPS) * U256::from(LOYALTY_RESERVE_PRECISION);
+        let denominator: U256 = U256::from(pool.config.reward_denumerator) * U256::from(LOYALTY_BASE_BPS);
+        let per_share_increase: U256 = (numerator + denominator - 1) / denominator;
+
+        let reserve_increase: U256 = (U256::from(pool.total_stake_balance) * per_share_increase + LOYALTY_RESERVE_PRECISION - 1) / U256::from(LOYALTY_RESERVE_PRECISION);
+
+        (per_share_increase.as_u128(), reserve_increase.as_u128())
+    }
+
+    pub(crate) fn internal_calculate_account_reward(&self, pool: &Pool, account: &Account) -> Balance {
+        let (reward, loyalty_bonus) = self.internal_calculate_account_reward_with_bonus(pool, account);
+
+        reward + loyalty_bonus + self.internal_calculate_penalty_share(pool, account)
+    }
+
+    // Trả về reward theo config và phần cộng thêm theo loyalty
+    pub(crate) fn internal_calculate_account_reward_with_bonus(&self, pool: &Pool, account: &Account) -> (Balance, Balance) {
+        let mut from_block: BlockHeight = account.last_block_balance_change;
+        let mut reward: Balance = 0;
+        let mut loyalty_bonus: Balance = 0;
+
+        // các đoạn config cũ account chưa chốt tính theo config của đoạn đó
+        for checkpoint in pool.reward_checkpoints.iter().filter(|checkpoint| checkpoint.end_block > account.last_block_balance_change) {
+            let (segment_reward, segment_bonus) = self.internal_calculate_segment_reward(&checkpoint.config, &checkpoint.loyalty, account, from_block, checkpoint.end_block);
+            reward += segment_reward;
+            loyalty_bonus += segment_bonus;
+            from_block = checkpoint.end_block;
+        }
+
+        let (segment_reward, segment_bonus) = self.internal_calculate_segment_reward(&pool.config, &pool.loyalty, account, from_block, pool.reward_block());
+
+        (reward + segment_reward, loyalty_bonus + segment_bonus)
+    }
+
+    fn internal_calculate_segment_reward(&self, config: &ConfigForReward, loyalty: &LoyaltyConfig, account: &Account, from_block: BlockHeight, to_block: BlockHeight) -> (Balance, Balance) {
+        let diff_block = to_block - from_block;
+        let reward: Balance = (account.stake_balance * config.reward_numerator as u128 * diff_block as u128) / config.reward_denumerator as u128;
+
+        let bonus_block = loyalty.bonus_blocks(
+            from_block.saturating_sub(account.loyalty_start_block),
+            to_block.saturating_sub(account.loyalty_start_block)
+        );
+        let loyalty_bonus: Balance = (account.stake_balance * config.reward_numerator as u128 * bonus_block as u128) / config.reward_denumerator as u128;
+
+        (reward, loyalty_bonus)
+    }
+
+    pub(crate) fn internal_calculate_fee(&self, reward: Balance) -> Balance {
+        reward * self.fee_bps as u128 / MAX_FEE_BPS as u128
+    }
+
+    // reward pool đã tích luỹ nhưng chưa trả cho user, gồm cả reward loyalty tối đa cho phần account chưa chốt
+    pub(crate) fn internal_calculate_unpaid_reward(&self, pool: &Pool) -> Balance {
+        let (_, loyalty_reserve) = self.internal_calculate_loyalty_reserve(pool);
+
+        (pool.pre_reward + self.internal_calculate_global_reward(pool) + pool.total_referral_reward_balance + pool.total_loyalty_bonus_balance
+            + pool.total_loyalty_reserve_balance + loyalty_reserve + pool.total_penalty_reward_balance)
+            .saturating_sub(pool.total_paid_reward_balance)
+    }
+
+    pub(crate) fn internal_calculate_global_reward(&self, pool: &Pool) -> Balance {
+        let lasted_block = pool.reward_block();
+
+        let diff_block = lasted_block - pool.last_block_balance_change;
+        let reward: Balance = (pool.total_stake_balance * pool.config.reward_numerator as u128 * diff_block as u128) / pool.config.reward_denumerator as u128;
 
         reward
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl StakingContract {
+
+    // Kiểm tra số liệu tổng của pool khớp với account, gọi trong unit test sau mỗi thay đổi state
+    pub(crate) fn assert_invariants(&self) {
+        for (pool_id, pool) in self.pools.iter().enumerate() {
+            let mut total_stake_balance: Balance = 0;
+            let mut total_unstake_balance: Balance = 0;
+            let mut total_staker: Balance = 0;
+            let mut total_vesting_balance: Balance = 0;
+            let mut total_loyalty_reserve_balance: Balance = 0;
+
+            for account_id in pool.account_ids.iter() {
+                let account: Account = self.internal_get_account(pool_id as PoolId, &account_id);
+                assert!(self.storage_accounts.contains_key(&account_id), "ERR_INVARIANT_ACCOUNT_NOT_REGISTERED");
+                assert!(account.last_block_balance_change <= pool.reward_block(), "ERR_INVARIANT_ACCOUNT_BLOCK");
+
+                total_stake_balance += account.stake_balance;
+                total_unstake_balance += account.unstake_balance;
+                total_vesting_balance += account.vesting.balance();
+                total_loyalty_reserve_balance += (U256::from(account.stake_balance) * U256::from(pool.loyalty_reserve_per_share - account.loyalty_reserve_per_share_paid) / U256::from(LOYALTY_RESERVE_PRECISION)).as_u128();
+                if account.stake_balance > 0 {
+                    total_staker += 1;
+                }
+            }
+
+            assert_eq!(pool.total_stake_balance, total_stake_balance, "ERR_INVARIANT_TOTAL_STAKE_BALANCE");
+            assert_eq!(pool.total_unstake_balance, total_unstake_balance, "ERR_INVARIANT_TOTAL_UNSTAKE_BALANCE");
+            assert_eq!(pool.total_staker, total_staker, "ERR_INVARIANT_TOTAL_STAKER");
+            assert_eq!(pool.total_vesting_balance, total_vesting_balance, "ERR_INVARIANT_TOTAL_VESTING_BALANCE");
+            assert!(pool.last_block_balance_change <= pool.reward_block(), "ERR_INVARIANT_POOL_BLOCK");
+            assert!(pool.total_loyalty_reserve_balance >= total_loyalty_reserve_balance, "ERR_INVARIANT_LOYALTY_RESERVE");
+        }
+    }
+}
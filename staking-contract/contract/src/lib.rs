@@ -1,41 +1,80 @@
-use near_sdk::collections::LookupMap;
-use near_sdk::{env, AccountId, Balance, BlockHeight, EpochHeight, PanicOnDefault, BorshStorageKey, near_bindgen, Promise, json_types::U128};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::{env, AccountId, Balance, BlockHeight, EpochHeight, StorageUsage, PanicOnDefault, BorshStorageKey, near_bindgen, Promise, json_types::U128};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::serde::{Deserialize, Serialize};
 
 use crate::config::*;
 use crate::account::*;
-use crate::util::*;
-use crate::internal::*;
-use crate::enumeration::*;
+use crate::pool::*;
+use crate::storage_impl::*;
 use crate::core_impl::*;
+use crate::event::*;
+use crate::role::*;
+use crate::timelock::*;
+use crate::rescue::*;
+use crate::treasury::*;
+use crate::allowlist::*;
+use crate::migration::*;
+use crate::vesting::*;
+use crate::operator::*;
+use crate::util::*;
 
 mod config;
 mod account;
+mod pool;
 mod util;
 mod internal;
 mod enumeration;
-mod core_impl;
+pub mod core_impl;
+mod storage_impl;
+mod event;
+mod migration;
+mod role;
+mod timelock;
+mod rescue;
+mod solvency;
+mod treasury;
+mod allowlist;
+mod vesting;
+mod withdrawal;
+mod operator;
 
 #[derive(BorshDeserialize, BorshSerialize, BorshStorageKey)]
 pub enum StorageKey {
-    AccountKey
+    AccountKey, // account của bản single pool cũ, giữ lại để không đổi prefix của các key phía sau
+    PoolKey,
+    PoolAccountKey,
+    StorageAccountKey,
+    PoolAccountIdsKey { pool_id: PoolId },
+    RoleKey,
+    ScheduledActionKey,
+    AllowlistKey,
+    ApprovalKey,
+    RewardCheckpointKey { pool_id: PoolId },
 }
 
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 #[near_bindgen]
 pub struct StakingContract {
     pub owner_id: AccountId,
+    pub pending_owner_id: Option<AccountId>, // owner mới đã được đề cử, chờ accept_owner
+    pub roles: UnorderedMap<AccountId, Vec<Role>>, // quyền admin của các account khác owner
     pub ft_contract_id: AccountId,
-    pub config: ConfigForReward, // cấu hình công thức trả thưởng cho user
-    pub total_stake_balance: Balance,
-    pub total_paid_reward_balance: Balance,
-    pub total_staker: Balance,
-    pub pre_reward: Balance,
-    pub last_block_balance_change: BlockHeight,
-    pub accounts: LookupMap<AccountId, UpgradableAccount>, // thông tin chi tiết của account map theo account id
-    pub paused: bool, // nếu hết token không thể trả cho user, pause contract, user sẽ không deposit thêm và reward cũng không trả thêm nữa
-    pub pause_in_block: BlockHeight,
+    pub staking_mode: StakingMode, // stake bằng ft token hay NEAR, reward luôn trả bằng ft token
+    pub pools: Vector<Pool>, // cấu hình và số liệu tổng của từng pool, pool id là index trong vector
+    pub accounts: LookupMap<(PoolId, AccountId), UpgradableAccount>, // thông tin chi tiết của account map theo pool id và account id
+    pub storage_accounts: LookupMap<AccountId, AccountStorage>, // NEAR deposit cho storage và số byte account đang dùng
+    pub account_storage_usage: StorageUsage, // số byte tối thiểu để đăng ký 1 account
+    pub timelock_delay: u64, // thời gian chờ (nano giây) từ lúc schedule đến lúc được execute admin action
+    pub scheduled_actions: UnorderedMap<u64, ScheduledAction>, // admin action đang chờ timelock, map theo action id
+    pub next_action_id: u64,
+    pub fee_bps: u32, // phí performance (basis point) trích từ reward khi harvest / compound
+    pub treasury_balance: Balance, // phí đã thu, chưa claim
+    pub in_flight_outflow: Balance, // ft token đã trừ khỏi số liệu của pool / treasury nhưng transfer chưa có kết quả
+    pub allowlist_enabled: bool, // bật thì chỉ account trong allowlist được đăng ký và stake
+    pub allowlist: UnorderedSet<AccountId>,
+    pub merkle_root: Option<Vec<u8>>, // root của merkle allowlist, account gửi proof khi stake
+    pub approvals: LookupMap<AccountId, Vec<OperatorApproval>>, // operator được account cho phép thao tác thay
 }
 
 #[near_bindgen]
@@ -48,64 +87,84 @@ impl StakingContract {
 
     #[init]
     pub fn new(owner_id: AccountId, ft_contract_id: AccountId, config: ConfigForReward) -> Self {
-        StakingContract {
+        Self::internal_new(owner_id, ft_contract_id, StakingMode::FungibleToken, config)
+    }
+
+    #[init]
+    pub fn new_native_staking(owner_id: AccountId, ft_contract_id: AccountId, config: ConfigForReward) -> Self {
+        Self::internal_new(owner_id, ft_contract_id, StakingMode::Native, config)
+    }
+
+    fn internal_new(owner_id: AccountId, ft_contract_id: AccountId, staking_mode: StakingMode, config: ConfigForReward) -> Self {
+        let mut contract = StakingContract {
             owner_id,
+            pending_owner_id: None,
+            roles: UnorderedMap::new(StorageKey::RoleKey),
             ft_contract_id,
-            config,
-            total_stake_balance: 0,
-            total_paid_reward_balance: 0,
-            total_staker: 0,
-            pre_reward: 0,
-            last_block_balance_change: env::block_height(),
-            accounts: LookupMap::new(StorageKey::AccountKey),
-            paused: false,
-            pause_in_block: 0,
-        }
+            staking_mode,
+            pools: Vector::new(StorageKey::PoolKey),
+            accounts: LookupMap::new(StorageKey::PoolAccountKey),
+            storage_accounts: LookupMap::new(StorageKey::StorageAccountKey),
+            account_storage_usage: 0,
+            timelock_delay: DEFAULT_TIMELOCK_DELAY,
+            scheduled_actions: UnorderedMap::new(StorageKey::ScheduledActionKey),
+            next_action_id: 0,
+            fee_bps: 0,
+            treasury_balance: 0,
+            in_flight_outflow: 0,
+            allowlist_enabled: false,
+            allowlist: UnorderedSet::new(StorageKey::AllowlistKey),
+            merkle_root: None,
+            approvals: LookupMap::new(StorageKey::ApprovalKey),
+        };
+
+        // pool đầu tiên (id 0) dùng config lúc init
+        contract.internal_create_pool(config);
+        contract.internal_measure_account_storage_usage();
+        write_state_version();
+
+        contract
     }
 
-    // để 1 fn deposit được thì sẽ phải có macro này
-    #[payable]
-    pub fn storage_deposit(&mut self, account_id: Option<AccountId>) {
-        assert_at_least_one_yocto();
-        let account = account_id.unwrap_or_else(|| env::predecessor_account_id());
-        let account_stake: Option<UpgradableAccount> = self.accounts.get(&account);
-
-        if account_stake.is_some() {
-            // refund toàn bộ token deposit
-            refund_deposit(0);
-        } else {
-            // Tạo account mới
-            let before_storage_useage: u64 = env::storage_usage();
-            self.internal_register_account(account.clone());
-            let after_storage_usage: u64 = env::storage_usage();
-
-            // Refund lại token deposit còn thừa
-            refund_deposit(after_storage_usage - before_storage_useage);
-        }
+    pub fn create_pool(&mut self, config: ConfigForReward) -> PoolId {
+        self.assert_role(Role::ConfigManager);
+
+        self.internal_create_pool(config)
     }
 
-    pub fn storage_balance_of(&mut self, account_id: AccountId) -> U128 {
-        let account: Option<UpgradableAccount> = self.accounts.get(&account_id);
+    pub fn pause(&mut self, pool_id: PoolId) {
+        self.assert_role(Role::Pauser);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
 
-        if account.is_some() {
-            U128(1)
-        } else {
-            U128(0)
-        }
+        pool.pause();
+        self.pools.replace(pool_id as u64, &pool);
+
+        EventLogVariant::Pause(vec![PoolLog { pool_id }]).emit();
+    }
+
+    pub fn resume(&mut self, pool_id: PoolId) {
+        self.assert_role(Role::Pauser);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+
+        pool.resume();
+        self.pools.replace(pool_id as u64, &pool);
+
+        EventLogVariant::Resume(vec![PoolLog { pool_id }]).emit();
     }
 
-    pub fn is_pause(&self) -> bool {
-        self.paused
+    pub fn is_pause(&self, pool_id: PoolId) -> bool {
+        self.internal_get_pool(pool_id).paused
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::config::*;
-
     use super::*;
-    use near_sdk::{testing_env, MockedBlockchain};
-    use near_sdk::test_utils::{ VMContextBuilder, accounts};
+    use crate::core_impl::FungibleTokenReceiver;
+    use near_sdk::{testing_env, Gas, ONE_NEAR, PromiseOrValue, PromiseResult, RuntimeFeesConfig, VMConfig};
+    use near_sdk::test_utils::{ VMContextBuilder, accounts, get_logs};
+    use near_sdk::json_types::{Base64VecU8, U64};
+    use near_sdk::serde_json;
 
     fn get_context(is_view: bool) -> VMContextBuilder {
         let mut builder = VMContextBuilder::new();
@@ -117,6 +176,28 @@ mod tests {
         builder
     }
 
+    fn ft_contract() -> AccountId {
+        AccountId::new_unchecked("ft_contract".to_string())
+    }
+
+    // Đặt lịch action rồi chạy ngay khi hết timelock
+    fn schedule_and_execute(contract: &mut StakingContract, context: &mut VMContextBuilder, action: AdminAction) {
+        let action_id = contract.schedule_action(action);
+        testing_env!(context.block_timestamp(env::block_timestamp() + DEFAULT_TIMELOCK_DELAY).build());
+        contract.execute_action(action_id);
+    }
+
+    // Đăng ký storage cho account rồi stake amount qua ft_on_transfer
+    fn setup_staker(contract: &mut StakingContract, context: &mut VMContextBuilder, account_id: AccountId, amount: Balance) {
+        testing_env!(context.predecessor_account_id(account_id.clone()).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(account_id, U128(amount), String::new());
+        contract.assert_invariants();
+    }
+
     #[test]
     fn test_init_contract() {
         let context = get_context(false);
@@ -127,11 +208,1860 @@ mod tests {
             reward_denumerator: 100000,
         };
 
-        let contract = StakingContract::new(AccountId::new_unchecked(accounts(1).to_string()), AccountId::new_unchecked("ft_contract".to_string()), config);
+        let contract = StakingContract::new(AccountId::new_unchecked(accounts(1).to_string()), ft_contract(), config);
 
         assert_eq!(contract.owner_id, AccountId::new_unchecked(accounts(1).to_string()));
-        assert_eq!(contract.ft_contract_id, AccountId::new_unchecked("ft_contract".to_string()));
-        assert_eq!(config.reward_numerator, contract.config.reward_numerator);
-        assert_eq!(contract.paused, false);
+        assert_eq!(contract.ft_contract_id, ft_contract());
+        assert_eq!(contract.pools.len(), 1);
+        assert_eq!(config.reward_numerator, contract.get_pool_info(0).config.reward_numerator);
+        assert!(!contract.is_pause(0));
+    }
+
+    #[test]
+    fn test_native_deposit_and_stake() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_native_staking(accounts(1), ft_contract(), ConfigForReward::default());
+        assert_eq!(contract.staking_mode, StakingMode::Native);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+
+        testing_env!(context.attached_deposit(10 * ONE_NEAR).build());
+        contract.deposit_and_stake(0, None, None);
+        contract.assert_invariants();
+
+        let account_json = contract.get_account_info(0, accounts(2));
+        assert_eq!(account_json.stake_balance, U128(10 * ONE_NEAR));
+
+        let pool_json = contract.get_pool_info(0);
+        assert_eq!(pool_json.total_stake_balance, U128(10 * ONE_NEAR));
+        assert_eq!(pool_json.total_stakers, U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_STAKING_MODE")]
+    fn test_native_mode_reject_ft_on_transfer() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_native_staking(accounts(1), ft_contract(), ConfigForReward::default());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(ONE_NEAR), String::new());
+    }
+
+    #[test]
+    fn test_stake_into_multiple_pools() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        let config: ConfigForReward = ConfigForReward {
+            reward_numerator: 1,
+            reward_denumerator: 1000,
+        };
+        let pool_id = contract.create_pool(config);
+        assert_eq!(pool_id, 1);
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+        contract.ft_on_transfer(accounts(2), U128(300), "{\"pool_id\": 1}".to_string());
+        contract.assert_invariants();
+
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(100));
+        assert_eq!(contract.get_account_info(pool_id, accounts(2)).stake_balance, U128(300));
+
+        let pools = contract.get_pools(None, None);
+        assert_eq!(pools.len(), 2);
+        assert_eq!(pools[0].total_stake_balance, U128(100));
+        assert_eq!(pools[1].total_stake_balance, U128(300));
+        assert_eq!(pools[1].config.reward_denumerator, 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn test_create_pool_not_owner() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.create_pool(ConfigForReward::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_CONFIG")]
+    fn test_create_pool_zero_denumerator() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.create_pool(ConfigForReward { reward_numerator: 715, reward_denumerator: 0 });
+    }
+
+    #[test]
+    fn test_storage_management() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let min_balance: Balance = contract.storage_balance_bounds().min.0;
+        assert!(min_balance > 0);
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+
+        // registration only chỉ giữ lại min balance
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR).build());
+        let storage_balance = contract.storage_deposit(None, Some(true));
+        contract.assert_invariants();
+        assert_eq!(storage_balance.total, U128(min_balance));
+
+        // deposit thêm để dùng cho pool thứ 2
+        testing_env!(context.attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(100), String::new());
+        contract.assert_invariants();
+
+        let storage_balance = contract.storage_balance_of(accounts(2)).unwrap();
+        assert_eq!(storage_balance.total, U128(min_balance + ONE_NEAR / 100));
+        assert!(storage_balance.available.0 < storage_balance.total.0);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        let storage_balance = contract.storage_withdraw(None);
+        contract.assert_invariants();
+        assert_eq!(storage_balance.available, U128(0));
+
+        // unstake và withdraw xong mới được unregister
+        contract.unstake(0, U128(100), None);
+        contract.assert_invariants();
+        testing_env!(context.epoch_height(1).attached_deposit(1).build());
+        let old_account = contract.internal_get_account(0, &accounts(2));
+        contract.withdraw(0);
+        contract.assert_invariants();
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_withdraw_callback(0, accounts(2), old_account);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        assert!(contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ACCOUNT_HAS_PENDING_TRANSFER")]
+    fn test_unregister_pending_withdraw() {
+        let mut context = get_context(false);
+        testing_env!(context.epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(100), None);
+        testing_env!(context.epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.withdraw(0);
+        contract.assert_invariants();
+
+        // withdraw chưa có callback, rollback còn cần account
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ACCOUNT_HAS_STAKE")]
+    fn test_storage_unregister_force_with_stake() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        // force không bỏ qua unbonding period
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DISABLE_WITHDRAW")]
+    fn test_storage_unregister_force_while_unbonding() {
+        let mut context = get_context(false);
+        testing_env!(context.epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(100), None);
+        contract.storage_unregister(Some(true));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ACCOUNT_HAS_BALANCE")]
+    fn test_storage_unregister_with_balance() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_storage_unregister_force() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        // force chỉ trả unstake balance đã hết unbonding cùng reward
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.unstake(0, U128(100), None);
+        testing_env!(context.epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        assert!(contract.storage_unregister(Some(true)));
+        contract.assert_invariants();
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(0));
+        assert_eq!(contract.get_pool_info(0).total_stakers, U128(0));
+
+        // ft_transfer lỗi thì account được đăng ký lại, unstake balance được trả lại
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let mut settled_account: Account = Account::new(0);
+        settled_account.unstake_balance = 100;
+        assert!(!contract.storage_unregister_callback(accounts(2), vec![(0, settled_account)], U128(ONE_NEAR / 100)));
+        contract.assert_invariants();
+
+        assert_eq!(contract.storage_balance_of(accounts(2)).unwrap().total, U128(ONE_NEAR / 100));
+        let account_json = contract.get_account_info(0, accounts(2));
+        assert_eq!(account_json.stake_balance, U128(0));
+        assert_eq!(account_json.unstake_balance, U128(100));
+        assert!(account_json.can_withdraw);
+    }
+
+    #[test]
+    fn test_stake_and_unstake_events() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+        assert_eq!(
+            get_logs(),
+            vec![format!(r#"EVENT_JSON:{{"standard":"staking","version":"1.0.0","event":"register","data":[{{"account_id":"charlie","amount":"{}"}}]}}"#, ONE_NEAR / 100)]
+        );
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(100), String::new());
+        contract.assert_invariants();
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"staking","version":"1.0.0","event":"stake","data":[{"pool_id":0,"account_id":"charlie","amount":"100"}]}"#]
+        );
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(40), None);
+        contract.assert_invariants();
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"staking","version":"1.0.0","event":"unstake","data":[{"pool_id":0,"account_id":"charlie","amount":"40"}]}"#]
+        );
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let config: ConfigForReward = ConfigForReward {
+            reward_numerator: 1,
+            reward_denumerator: 100,
+        };
+        let mut contract = StakingContract::new(accounts(1), ft_contract(), config);
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(10).build());
+        contract.pause(0);
+        contract.assert_invariants();
+        assert!(contract.is_pause(0));
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"staking","version":"1.0.0","event":"pause","data":[{"pool_id":0}]}"#]
+        );
+
+        // không tính reward trong lúc pause
+        testing_env!(context.block_index(30).build());
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 100);
+
+        contract.resume(0);
+        contract.assert_invariants();
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"staking","version":"1.0.0","event":"resume","data":[{"pool_id":0}]}"#]
+        );
+
+        testing_env!(context.block_index(40).build());
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 200);
+        assert_eq!(contract.get_pool_info(0).total_reward, U128(200));
+    }
+
+    #[test]
+    fn test_harvest_callback_failed_event() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).block_index(0).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(10).build());
+        let reward = contract.get_account_reward(0, accounts(2));
+        contract.harvest(0, None);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.ft_transfer_callback(0, U128(reward), accounts(2), U128(0), U128(0)), U128(0));
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(2)), reward);
+        assert_eq!(
+            get_logs(),
+            vec![format!(r#"EVENT_JSON:{{"standard":"staking","version":"1.0.0","event":"callback_failed","data":[{{"callback":"ft_transfer_callback","account_id":"charlie","pool_id":0,"amount":"{}"}}]}}"#, reward)]
+        );
+    }
+
+    #[test]
+    fn test_get_accounts() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        for index in 2..5 {
+            setup_staker(&mut contract, &mut context, accounts(index), 100 * index as u128);
+        }
+
+        assert_eq!(contract.get_accounts_count(0), U128(3));
+
+        let page = contract.get_accounts(0, Some(U128(1)), Some(5));
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].account_id, accounts(3));
+        assert_eq!(page[1].stake_balance, U128(400));
+    }
+
+    #[test]
+    fn test_migrate_from_single_pool() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut old_accounts: LookupMap<AccountId, UpgradableAccount> = LookupMap::new(StorageKey::AccountKey);
+        old_accounts.insert(&accounts(2), &UpgradableAccount::V1(AccountV1 {
+            stake_balance: 100,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            unstake_balance: 30,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch: 0,
+        }));
+
+        env::state_write(&StakingContractV1 {
+            owner_id: accounts(1),
+            ft_contract_id: ft_contract(),
+            config: ConfigForReward::default(),
+            total_stake_balance: 100,
+            total_paid_reward_balance: 0,
+            total_staker: 1,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            accounts: old_accounts,
+            paused: false,
+            pause_in_block: 0,
+        });
+
+        let mut contract = StakingContract::migrate(Some(U128(30)));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(100));
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(30));
+        assert_eq!(contract.get_accounts_count(0), U128(0));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        assert_eq!(contract.migrate_accounts(vec![accounts(2), accounts(3)]), 1);
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(30));
+
+        assert_eq!(contract.get_accounts_count(0), U128(1));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(100));
+
+        let storage_balance = contract.storage_balance_of(accounts(2)).unwrap();
+        assert_eq!(storage_balance.available, U128(0));
+
+        assert_eq!(env::storage_read(STATE_VERSION_KEY), Some(vec![CURRENT_STATE_VERSION]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_TOTAL_UNSTAKE_BALANCE")]
+    fn test_migrate_from_single_pool_without_total_unstake() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        env::state_write(&StakingContractV1 {
+            owner_id: accounts(1),
+            ft_contract_id: ft_contract(),
+            config: ConfigForReward::default(),
+            total_stake_balance: 0,
+            total_paid_reward_balance: 0,
+            total_staker: 0,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            accounts: LookupMap::new(StorageKey::AccountKey),
+            paused: false,
+            pause_in_block: 0,
+        });
+
+        StakingContract::migrate(None);
+    }
+
+    #[test]
+    fn test_migrate_on_access() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut old_accounts: LookupMap<AccountId, UpgradableAccount> = LookupMap::new(StorageKey::AccountKey);
+        old_accounts.insert(&accounts(2), &UpgradableAccount::V1(AccountV1 {
+            stake_balance: 100,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            unstake_balance: 0,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch: 0,
+        }));
+
+        env::state_write(&StakingContractV1 {
+            owner_id: accounts(1),
+            ft_contract_id: ft_contract(),
+            config: ConfigForReward::default(),
+            total_stake_balance: 100,
+            total_paid_reward_balance: 0,
+            total_staker: 1,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            accounts: old_accounts,
+            paused: false,
+            pause_in_block: 0,
+        });
+
+        let mut contract = StakingContract::migrate(Some(U128(0)));
+
+        // account cũ đọc được và coi như đã đăng ký storage trước khi migrate
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(100));
+        assert!(contract.storage_balance_of(accounts(2)).is_some());
+
+        // không cần migrate_accounts hay storage_deposit lại
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(40), None);
+        contract.assert_invariants();
+
+        assert_eq!(contract.get_accounts_count(0), U128(1));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(60));
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(40));
+        assert_eq!(contract.storage_balance_of(accounts(2)).unwrap().available, U128(0));
+
+        testing_env!(context.block_index(10).epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.withdraw(0);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(2)).unstake_balance, U128(0));
+
+        // migrate lại không ghi đè account đã migrate
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(0).build());
+        assert_eq!(contract.migrate_accounts(vec![accounts(2)]), 0);
+        contract.assert_invariants();
+    }
+
+    #[test]
+    fn test_migrate_current_state() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        testing_env!(context.attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(Some(accounts(0)), None);
+        contract.assert_invariants();
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(0), U128(100), String::new());
+        contract.assert_invariants();
+        env::state_write(&contract);
+
+        let mut contract = StakingContract::migrate(None);
+        contract.assert_invariants();
+        assert_eq!(env::storage_read(STATE_VERSION_KEY), Some(vec![CURRENT_STATE_VERSION]));
+        assert_eq!(contract.get_account_info(0, accounts(0)).stake_balance, U128(100));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(100));
+
+        // account V1 vẫn đọc được và được chuyển sang Current ở lần ghi tiếp theo
+        contract.accounts.insert(&(0, accounts(0)), &UpgradableAccount::V1(AccountV1 {
+            stake_balance: 100,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            unstake_balance: 0,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch: 0,
+        }));
+        testing_env!(context.predecessor_account_id(accounts(0)).attached_deposit(1).build());
+        contract.unstake(0, U128(40), None);
+        contract.assert_invariants();
+        match contract.accounts.get(&(0, accounts(0))).unwrap() {
+            UpgradableAccount::Current(account) => assert_eq!(account.unstake_balance, 40),
+            UpgradableAccount::V1(_) => panic!("account not upgraded"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn test_upgrade_not_owner() {
+        let context = get_context(false);
+        testing_env!(context.build());
+
+        let contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.upgrade();
+    }
+
+    #[test]
+    fn test_transfer_owner() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.propose_owner(accounts(2));
+        assert_eq!(contract.get_roles().pending_owner_id, Some(accounts(2)));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.accept_owner();
+        assert_eq!(contract.get_roles().owner_id, accounts(2));
+        assert_eq!(contract.get_roles().pending_owner_id, None);
+
+        // owner cũ không còn quyền admin
+        assert!(!contract.has_role(accounts(1), Role::Pauser));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PENDING_OWNER")]
+    fn test_accept_owner_not_pending() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.propose_owner(accounts(2));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).build());
+        contract.accept_owner();
+    }
+
+    #[test]
+    fn test_roles() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.grant_role(accounts(2), Role::Pauser);
+        contract.grant_role(accounts(3), Role::RewardFunder);
+        assert_eq!(contract.get_roles().accounts.len(), 2);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.pause(0);
+        contract.assert_invariants();
+        assert!(contract.is_pause(0));
+        assert!(!contract.has_role(accounts(2), Role::ConfigManager));
+
+        // reward funder nạp token vào quỹ reward qua ft_transfer_call
+        testing_env!(context.predecessor_account_id(ft_contract()).build());
+        contract.ft_on_transfer(accounts(3), U128(500), String::from("{\"pool_id\":0,\"fund_reward\":true}"));
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_funded_reward, U128(500));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(0));
+
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.revoke_role(accounts(2), Role::Pauser);
+        assert!(!contract.has_role(accounts(2), Role::Pauser));
+        assert!(contract.has_role(accounts(1), Role::Upgrader));
+        assert_eq!(contract.get_roles().accounts.len(), 1);
+    }
+
+    #[test]
+    fn test_native_mode_fund_reward() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_native_staking(accounts(1), ft_contract(), ConfigForReward::default());
+        contract.grant_role(accounts(3), Role::RewardFunder);
+
+        testing_env!(context.predecessor_account_id(ft_contract()).build());
+        contract.ft_on_transfer(accounts(3), U128(500), String::from("{\"fund_reward\":true}"));
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_funded_reward, U128(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn test_fund_reward_missing_role() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(ft_contract()).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.ft_on_transfer(accounts(3), U128(500), String::from("{\"fund_reward\":true}"));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_timelock_update_config() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let config = ConfigForReward { reward_numerator: 1000, reward_denumerator: 100000000000 };
+        let action_id = contract.schedule_action(AdminAction::Config { pool_id: 0, config });
+        contract.schedule_action(AdminAction::UnbondingPeriod { pool_id: 0, unbonding_period: 4 });
+
+        let scheduled_actions = contract.get_scheduled_actions();
+        assert_eq!(scheduled_actions.len(), 2);
+        assert_eq!(scheduled_actions[0].eta.0, DEFAULT_TIMELOCK_DELAY);
+
+        testing_env!(context.block_timestamp(DEFAULT_TIMELOCK_DELAY).build());
+        contract.execute_action(action_id);
+        contract.assert_invariants();
+        contract.cancel_action(U64(1));
+
+        assert_eq!(contract.get_pool_info(0).config.reward_numerator, 1000);
+        assert_eq!(contract.get_pool_info(0).unbonding_period, DEFAULT_UNBONDING_PERIOD);
+        assert!(contract.get_scheduled_actions().is_empty());
+    }
+
+    #[test]
+    fn test_update_config_keeps_old_rate() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        // đổi config ở block 100, account chưa chốt reward
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(100).build());
+        let old_reward = contract.get_account_reward(0, accounts(2));
+        let config = ConfigForReward { reward_numerator: 1430, reward_denumerator: 100000000000 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::Config { pool_id: 0, config });
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(2)), old_reward);
+
+        // 100 block sau tính theo config mới gấp đôi
+        testing_env!(context.block_index(200).build());
+        assert_eq!(contract.get_account_reward(0, accounts(2)), old_reward * 3);
+        assert_eq!(contract.internal_calculate_unpaid_reward(&contract.internal_get_pool(0)), old_reward * 3);
+    }
+
+    #[test]
+    fn test_reward_checkpoints_capped() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        // đổi config xen kẽ rate thấp / cao, account không chốt reward lần nào
+        let low_config = ConfigForReward { reward_numerator: 715, reward_denumerator: 100000000000 };
+        let high_config = ConfigForReward { reward_numerator: 1430, reward_denumerator: 100000000000 };
+        for index in 1..=MAX_REWARD_CHECKPOINTS + 1 {
+            testing_env!(context.predecessor_account_id(accounts(1)).block_index(index * 10).build());
+            let config = if index % 2 == 0 { low_config } else { high_config };
+            schedule_and_execute(&mut contract, &mut context, AdminAction::Config { pool_id: 0, config });
+            contract.assert_invariants();
+        }
+
+        let pool = contract.internal_get_pool(0);
+        assert_eq!(pool.reward_checkpoints.len(), MAX_REWARD_CHECKPOINTS);
+        // 2 đoạn cũ nhất được gộp theo rate thấp hơn
+        let checkpoint = pool.reward_checkpoints.get(0).unwrap();
+        assert_eq!(checkpoint.end_block, 20);
+        assert_eq!(checkpoint.config.reward_numerator, low_config.reward_numerator);
+
+        let reward = contract.get_account_reward(0, accounts(2));
+        assert!(reward > 0);
+        assert!(reward <= contract.internal_calculate_unpaid_reward(&pool));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TIMELOCK_NOT_EXPIRED")]
+    fn test_timelock_execute_before_eta() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let action_id = contract.schedule_action(AdminAction::UnbondingPeriod { pool_id: 0, unbonding_period: 4 });
+
+        testing_env!(context.block_timestamp(DEFAULT_TIMELOCK_DELAY - 1).build());
+        contract.execute_action(action_id);
+    }
+
+    #[test]
+    fn test_emergency_withdraw() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.unstake(0, U128(200 * ONE_NEAR), None);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(0).build());
+        contract.pause(0);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        let old_account = contract.internal_emergency_withdraw(0, accounts(2));
+        contract.assert_invariants();
+        assert!(old_account.pre_reward > 0);
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(0));
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 0);
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(0));
+        assert_eq!(contract.get_pool_info(0).total_stakers, U128(0));
+
+        // transfer lỗi thì trả lại toàn bộ balance và reward
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        let reward = old_account.pre_reward;
+        assert_eq!(contract.emergency_withdraw_callback(0, accounts(2), old_account), U128(0));
+
+        let account = contract.get_account_info(0, accounts(2));
+        assert_eq!(account.stake_balance, U128(800 * ONE_NEAR));
+        assert_eq!(account.unstake_balance, U128(200 * ONE_NEAR));
+        assert_eq!(account.reward, U128(reward));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(800 * ONE_NEAR));
+        assert_eq!(contract.get_pool_info(0).total_stakers, U128(1));
+    }
+
+    #[test]
+    fn test_emergency_withdraw_solvency() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(100).build());
+        contract.pause(0);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        let old_account = contract.internal_emergency_withdraw(0, accounts(2));
+        contract.assert_invariants();
+        assert!(old_account.pre_reward > 0);
+        assert_eq!(contract.internal_solvency_report(1000 * ONE_NEAR).total_unpaid_reward, U128(old_account.pre_reward));
+
+        // transfer thành công thì reward bị bỏ không còn tính vào nợ của pool
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.emergency_withdraw_callback(0, accounts(2), old_account);
+        contract.assert_invariants();
+
+        let report = contract.internal_solvency_report(0);
+        assert_eq!(report.total_unpaid_reward, U128(0));
+        assert_eq!(report.total_liabilities, U128(0));
+        assert!(report.is_solvent);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_POOL_NOT_PAUSED")]
+    fn test_emergency_withdraw_not_paused() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 100);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.emergency_withdraw(0);
+    }
+
+    #[test]
+    fn test_rescue_tokens_surplus() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000);
+        contract.ft_on_transfer(accounts(1), U128(300), String::from("{\"fund_reward\":true}"));
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(400), None);
+        contract.assert_invariants();
+        assert_eq!(contract.internal_committed_ft_balance(), 1300);
+
+        // unstake balance đang withdraw vẫn nằm trong số dư của contract
+        testing_env!(context.epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.withdraw(0);
+        contract.assert_invariants();
+        assert_eq!(contract.internal_committed_ft_balance(), 1300);
+
+        // contract đang giữ 1500 token, dư 200
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"1500\"".to_vec())]
+        );
+        contract.rescue_tokens_callback(ft_contract(), U128(200), accounts(1));
+        assert_eq!(
+            get_logs(),
+            vec![format!(r#"EVENT_JSON:{{"standard":"staking","version":"1.0.0","event":"rescue_tokens","data":[{{"token_id":"{}","receiver_id":"{}","amount":"200"}}]}}"#, ft_contract(), accounts(1))]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_GREATER_THAN_SURPLUS")]
+    fn test_rescue_tokens_above_surplus() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000);
+        contract.ft_on_transfer(accounts(1), U128(300), String::from("{\"fund_reward\":true}"));
+        contract.assert_invariants();
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"1500\"".to_vec())]
+        );
+        contract.rescue_tokens_callback(ft_contract(), U128(201), accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_GREATER_THAN_SURPLUS")]
+    fn test_rescue_tokens_principal() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"1100\"".to_vec())]
+        );
+        contract.rescue_tokens_callback(ft_contract(), U128(101), accounts(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_GREATER_THAN_SURPLUS")]
+    fn test_rescue_tokens_legacy_unstake() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut old_accounts: LookupMap<AccountId, UpgradableAccount> = LookupMap::new(StorageKey::AccountKey);
+        old_accounts.insert(&accounts(2), &UpgradableAccount::V1(AccountV1 {
+            stake_balance: 100,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            unstake_balance: 30,
+            unstake_start_timestamp: 0,
+            unstake_available_epoch: 0,
+        }));
+
+        env::state_write(&StakingContractV1 {
+            owner_id: accounts(1),
+            ft_contract_id: ft_contract(),
+            config: ConfigForReward::default(),
+            total_stake_balance: 100,
+            total_paid_reward_balance: 0,
+            total_staker: 1,
+            pre_reward: 0,
+            last_block_balance_change: 0,
+            accounts: old_accounts,
+            paused: false,
+            pause_in_block: 0,
+        });
+
+        // unstake balance của account chưa migrate vẫn là token contract phải giữ
+        let mut contract = StakingContract::migrate(Some(U128(30)));
+        assert_eq!(contract.internal_committed_ft_balance(), 130);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(b"\"130\"".to_vec())]
+        );
+        contract.rescue_tokens_callback(ft_contract(), U128(1), accounts(1));
+    }
+
+    #[test]
+    fn test_total_unstake_balance() {
+        let mut context = get_context(false);
+        testing_env!(context.epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(300), None);
+        contract.assert_invariants();
+        contract.unstake(0, U128(100), None);
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(600));
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(400));
+
+        testing_env!(context.epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        let old_account = contract.internal_withdraw(0, accounts(2));
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(0));
+
+        // withdraw lỗi thì cộng lại vào tổng unstake
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_withdraw_callback(0, accounts(2), old_account);
+        contract.assert_invariants();
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(400));
+        assert_eq!(contract.get_account_info(0, accounts(2)).unstake_balance, U128(400));
+    }
+
+    #[test]
+    fn test_solvency_report() {
+        let mut context = get_context(false);
+        testing_env!(context.block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.unstake(0, U128(400 * ONE_NEAR), None);
+        contract.assert_invariants();
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(format!("\"{}\"", 1000 * ONE_NEAR).into_bytes())]
+        );
+        let report = contract.solvency_report_callback();
+        let reward = contract.get_account_reward(0, accounts(2));
+
+        assert!(reward > 0);
+        assert_eq!(report.total_stake_balance, U128(600 * ONE_NEAR));
+        assert_eq!(report.total_unstake_balance, U128(400 * ONE_NEAR));
+        assert_eq!(report.total_unpaid_reward, U128(reward));
+        assert_eq!(report.deficit, U128(reward));
+        assert!(!report.is_solvent);
+
+        // unstake balance đang withdraw vẫn nằm trong số dư của contract nên vẫn tính vào nợ
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.withdraw(0);
+        contract.assert_invariants();
+
+        let report = contract.internal_solvency_report(1000 * ONE_NEAR);
+        assert_eq!(report.total_unstake_balance, U128(0));
+        assert_eq!(report.in_flight_outflow, U128(400 * ONE_NEAR));
+        assert_eq!(report.total_liabilities, U128(1000 * ONE_NEAR + reward));
+        assert_eq!(report.deficit, U128(reward));
+    }
+
+    #[test]
+    fn test_performance_fee() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        schedule_and_execute(&mut contract, &mut context, AdminAction::PerformanceFee { fee_bps: 1000 });
+        contract.assert_invariants();
+        assert_eq!(contract.get_treasury().fee_bps, 1000);
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        // compound: 10% reward vào treasury, phần còn lại cộng vào stake
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        let reward = contract.get_account_reward(0, accounts(2));
+        let amount = contract.compound(0, None);
+        contract.assert_invariants();
+        assert_eq!(amount.0, reward - reward / 10);
+        assert_eq!(contract.get_treasury().treasury_balance, U128(reward / 10));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(1000 * ONE_NEAR + amount.0));
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 0);
+
+        // harvest callback thành công cũng cộng phí vào treasury
+        testing_env!(context.block_index(200).build());
+        let harvest_reward = contract.get_account_reward(0, accounts(2));
+        contract.harvest(0, None);
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(0, U128(harvest_reward - harvest_reward / 10), accounts(2), U128(harvest_reward / 10), U128(0));
+        contract.assert_invariants();
+        let treasury_balance = contract.get_treasury().treasury_balance.0;
+        assert_eq!(treasury_balance, reward / 10 + harvest_reward / 10);
+
+        // claim lỗi thì trả lại treasury
+        testing_env!(context.predecessor_account_id(accounts(1)).attached_deposit(1).build());
+        contract.claim_treasury(Some(U128(10)));
+        assert_eq!(contract.get_treasury().treasury_balance, U128(treasury_balance - 10));
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        assert_eq!(contract.claim_treasury_callback(accounts(1), U128(10)), U128(0));
+        assert_eq!(contract.get_treasury().treasury_balance, U128(treasury_balance));
+        assert_eq!(contract.in_flight_outflow, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_ROLE")]
+    fn test_claim_treasury_missing_role() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.claim_treasury(None);
+    }
+
+    #[test]
+    fn test_stake_limits() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = StakeLimits { min_stake_amount: U128(100), deposit_fee_bps: 100, max_total_stake: Some(U128(1000)), max_account_stake: None };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::StakeLimits { pool_id: 0, limits });
+        contract.assert_invariants();
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+        }
+
+        // 1% phí stake vào treasury
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        let refund = contract.ft_on_transfer(accounts(2), U128(800), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(792));
+        assert_eq!(contract.get_treasury().treasury_balance, U128(8));
+
+        // pool còn trống 208, phần vượt quá được trả lại
+        let refund = contract.ft_on_transfer(accounts(3), U128(500), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(292))));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(998));
+
+        let refund = contract.ft_on_transfer(accounts(3), U128(500), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(498))));
+
+        // pool đầy thì trả lại toàn bộ
+        let refund = contract.ft_on_transfer(accounts(3), U128(500), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(500))));
+        assert_eq!(contract.get_pool_info(0).total_stake_balance, U128(1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_LESS_THAN_MIN_STAKE")]
+    fn test_min_stake_amount() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = StakeLimits { min_stake_amount: U128(100), deposit_fee_bps: 0, max_total_stake: None, max_account_stake: None };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::StakeLimits { pool_id: 0, limits });
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(99), String::new());
+    }
+
+    #[test]
+    fn test_allowlist_and_account_cap() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = StakeLimits { min_stake_amount: U128(0), deposit_fee_bps: 0, max_total_stake: None, max_account_stake: Some(U128(500)) };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::StakeLimits { pool_id: 0, limits });
+        contract.set_allowlist_enabled(true);
+        contract.add_to_allowlist(vec![accounts(2)]);
+
+        assert!(contract.is_allowlisted(accounts(2)));
+        assert!(!contract.is_allowlisted(accounts(3)));
+        assert_eq!(contract.get_allowlist(None, None), vec![accounts(2)]);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        let refund = contract.ft_on_transfer(accounts(2), U128(300), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0))));
+
+        let refund = contract.ft_on_transfer(accounts(2), U128(300), String::new());
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(100))));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ALLOWLISTED")]
+    fn test_storage_deposit_not_allowlisted() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        contract.set_allowlist_enabled(true);
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+    }
+
+    #[test]
+    fn test_merkle_allowlist() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        // cây 2 leaf: charlie có cap 300, danny không có cap
+        let leaf_charlie = merkle_leaf(&accounts(2), Some(U128(300)));
+        let leaf_danny = merkle_leaf(&accounts(3), None);
+        let merkle_root = merkle_parent(&leaf_charlie, &leaf_danny);
+
+        contract.set_allowlist_enabled(true);
+        contract.set_merkle_root(Some(Base64VecU8(merkle_root.clone())));
+        assert_eq!(contract.get_merkle_root().unwrap().0, merkle_root);
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+        }
+
+        let proof_charlie = AllowlistProof { proof: vec![Base64VecU8(leaf_danny)], cap: Some(U128(300)) };
+        let msg = serde_json::json!({ "pool_id": 0, "proof": proof_charlie }).to_string();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        let refund = contract.ft_on_transfer(accounts(2), U128(500), msg);
+        contract.assert_invariants();
+        assert!(matches!(refund, PromiseOrValue::Value(U128(200))));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(300));
+
+        let proof_danny = AllowlistProof { proof: vec![Base64VecU8(leaf_charlie)], cap: None };
+        let msg = serde_json::json!({ "pool_id": 0, "proof": proof_danny }).to_string();
+        contract.ft_on_transfer(accounts(3), U128(500), msg);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(3)).stake_balance, U128(500));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_MERKLE_PROOF")]
+    fn test_merkle_allowlist_invalid_proof() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let leaf_charlie = merkle_leaf(&accounts(2), Some(U128(300)));
+        let leaf_danny = merkle_leaf(&accounts(3), None);
+
+        contract.set_allowlist_enabled(true);
+        contract.set_merkle_root(Some(Base64VecU8(merkle_parent(&leaf_charlie, &leaf_danny))));
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+
+        // tự nâng cap lên thì proof không còn đúng
+        let proof = AllowlistProof { proof: vec![Base64VecU8(leaf_danny)], cap: Some(U128(1000)) };
+        let msg = serde_json::json!({ "proof": proof }).to_string();
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(500), msg);
+    }
+
+    #[test]
+    fn test_referral_reward() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        schedule_and_execute(&mut contract, &mut context, AdminAction::ReferralRate { pool_id: 0, referral_bps: 1000 });
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+        }
+
+        // danny được charlie giới thiệu, referrer chỉ lưu ở lần stake đầu
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+        contract.ft_on_transfer(accounts(3), U128(1000 * ONE_NEAR), format!("{{\"referrer_id\":\"{}\"}}", accounts(2)));
+        contract.assert_invariants();
+        contract.ft_on_transfer(accounts(3), U128(1000 * ONE_NEAR), format!("{{\"referrer_id\":\"{}\"}}", accounts(4)));
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(3)).referrer_id, Some(accounts(2)));
+
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).block_index(100).build());
+        let reward = contract.get_account_reward(0, accounts(3));
+        contract.compound(0, None);
+        contract.assert_invariants();
+
+        let referrer = contract.get_account_info(0, accounts(2));
+        assert_eq!(referrer.referral_reward, U128(reward / 10));
+        assert_eq!(contract.get_account_reward(0, accounts(2)), referrer.reward.0 + reward / 10);
+
+        // harvest trả cả reward giới thiệu, reward được trừ ngay lúc harvest
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.harvest(0, None);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(2)).referral_reward, U128(0));
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 0);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
+        );
+        contract.ft_transfer_callback(0, U128(referrer.reward.0 + reward / 10), accounts(2), U128(0), U128(reward / 10));
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_REFERRER")]
+    fn test_self_referral() {
+        let mut context = get_context(false);
+        testing_env!(context.build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(ONE_NEAR / 100).build());
+        contract.storage_deposit(None, None);
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(100), format!("{{\"referrer_id\":\"{}\"}}", accounts(2)));
+    }
+
+    #[test]
+    fn test_loyalty_multiplier() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let loyalty = LoyaltyConfig { max_multiplier_bps: 20_000, blocks_to_max: 100 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::Loyalty { pool_id: 0, loyalty });
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+        }
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+        contract.ft_on_transfer(accounts(3), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+
+        // multiplier tăng tuyến tính từ 1x lên 2x trong 100 block nên reward trung bình là 1.5x
+        testing_env!(context.block_index(100).build());
+        let base_reward = contract.get_account_reward(0, accounts(2)) * 2 / 3;
+        assert_eq!(contract.get_account_info(0, accounts(2)).loyalty_multiplier_bps, 20_000);
+
+        // sau khi đạt max, reward tăng theo 2x
+        testing_env!(context.block_index(200).build());
+        assert_eq!(contract.get_account_reward(0, accounts(2)), base_reward * 3 / 2 + base_reward * 2);
+
+        // reward loyalty chưa chốt vẫn nằm trong phần reward pool phải trả, giữ theo max multiplier
+        let unpaid_reward = contract.internal_calculate_unpaid_reward(&contract.internal_get_pool(0));
+        assert!(unpaid_reward >= contract.get_account_reward(0, accounts(2)) + contract.get_account_reward(0, accounts(3)));
+        assert!(unpaid_reward <= base_reward * 8 + 2);
+
+        // unstake reset loyalty nhưng giữ reward đã tích luỹ
+        testing_env!(context.predecessor_account_id(accounts(3)).attached_deposit(1).build());
+        contract.unstake(0, U128(ONE_NEAR), None);
+        contract.assert_invariants();
+        let account = contract.get_account_info(0, accounts(3));
+        assert_eq!(account.loyalty_multiplier_bps, 10_000);
+        assert_eq!(account.reward, U128(base_reward * 3 / 2 + base_reward * 2));
+    }
+
+    #[test]
+    fn test_loyalty_top_up() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let loyalty = LoyaltyConfig { max_multiplier_bps: 20_000, blocks_to_max: 100 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::Loyalty { pool_id: 0, loyalty });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        // nạp thêm bằng số stake cũ ở block 100, block bắt đầu loyalty lùi về giữa
+        testing_env!(context.block_index(100).build());
+        assert_eq!(contract.get_account_info(0, accounts(2)).loyalty_multiplier_bps, 20_000);
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+
+        assert_eq!(contract.internal_get_account(0, &accounts(2)).loyalty_start_block, 50);
+        assert_eq!(contract.get_account_info(0, accounts(2)).loyalty_multiplier_bps, 15_000);
+    }
+
+    #[test]
+    fn test_vesting_harvest() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let vesting = VestingConfig { cliff_blocks: 10, duration_blocks: 100, early_claim_penalty_bps: 5000 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::Vesting { pool_id: 0, vesting: Some(vesting) });
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+        }
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+        contract.ft_on_transfer(accounts(3), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+
+        // harvest chuyển reward vào lịch vesting, không transfer
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        let amount = match contract.harvest(0, None) {
+            PromiseOrValue::Value(amount) => amount.0,
+            PromiseOrValue::Promise(_) => panic!("harvest must not transfer when vesting"),
+        };
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(2)), 0);
+        assert_eq!(contract.get_vesting(0, accounts(2)).locked_balance, U128(amount));
+
+        // chưa qua cliff thì chưa mở khoá
+        testing_env!(context.block_index(105).build());
+        assert_eq!(contract.get_vesting(0, accounts(2)).claimable_balance, U128(0));
+
+        testing_env!(context.block_index(150).build());
+        assert_eq!(contract.get_vesting(0, accounts(2)).claimable_balance, U128(amount / 2));
+        contract.claim_vested(0, None);
+        contract.assert_invariants();
+        let vesting_json = contract.get_vesting(0, accounts(2));
+        assert_eq!(vesting_json.claimable_balance, U128(0));
+        assert_eq!(vesting_json.locked_balance, U128(amount - amount / 2));
+
+        // claim sớm mất 50% phần còn khoá, chia đều cho 2 staker
+        let reward_before = contract.get_account_reward(0, accounts(3));
+        let penalty = (amount - amount / 2) / 2;
+        contract.claim_vested(0, Some(true));
+        contract.assert_invariants();
+        assert_eq!(contract.get_vesting(0, accounts(2)).locked_balance, U128(0));
+        assert_eq!(contract.get_account_reward(0, accounts(3)), reward_before + penalty / 2);
+
+        // transfer lỗi thì trả lại phần đã rút vào claimable
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.claim_vested_callback(0, accounts(2), U128(amount - amount / 2 - penalty));
+        contract.assert_invariants();
+        assert_eq!(contract.get_vesting(0, accounts(2)).claimable_balance, U128(amount - amount / 2 - penalty));
+    }
+
+    #[test]
+    fn test_vesting_harvest_tranches() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let vesting = VestingConfig { cliff_blocks: 10, duration_blocks: 100, early_claim_penalty_bps: 5000 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::Vesting { pool_id: 0, vesting: Some(vesting) });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        let first_amount = match contract.harvest(0, None) {
+            PromiseOrValue::Value(amount) => amount.0,
+            PromiseOrValue::Promise(_) => panic!("harvest must not transfer when vesting"),
+        };
+        contract.assert_invariants();
+
+        // harvest lần 2 thêm tranche mới, tranche cũ giữ nguyên lịch
+        testing_env!(context.block_index(150).build());
+        let second_amount = match contract.harvest(0, None) {
+            PromiseOrValue::Value(amount) => amount.0,
+            PromiseOrValue::Promise(_) => panic!("harvest must not transfer when vesting"),
+        };
+        contract.assert_invariants();
+        let vesting_json = contract.get_vesting(0, accounts(2));
+        assert_eq!(vesting_json.tranches.len(), 2);
+        assert_eq!(vesting_json.claimable_balance, U128(first_amount / 2));
+
+        testing_env!(context.block_index(200).build());
+        let vesting_json = contract.get_vesting(0, accounts(2));
+        assert_eq!(vesting_json.tranches.len(), 1);
+        assert_eq!(vesting_json.claimable_balance, U128(first_amount + second_amount / 2));
+        assert_eq!(vesting_json.locked_balance, U128(second_amount - second_amount / 2));
+    }
+
+    #[test]
+    fn test_harvest_limits() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = HarvestLimits { min_harvest_amount: U128(1), cooldown_blocks: 50 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::HarvestLimits { pool_id: 0, limits });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+        assert_eq!(contract.get_account_info(0, accounts(2)).next_harvest_block, 0);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.harvest(0, None);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(2)).next_harvest_block, 150);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_HARVEST_COOLDOWN")]
+    fn test_harvest_cooldown() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = HarvestLimits { min_harvest_amount: U128(0), cooldown_blocks: 50 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::HarvestLimits { pool_id: 0, limits });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.harvest(0, None);
+        testing_env!(context.block_index(149).build());
+        contract.harvest(0, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_LESS_THAN_MIN_HARVEST")]
+    fn test_harvest_less_than_min_amount() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        let limits = HarvestLimits { min_harvest_amount: U128(u128::MAX), cooldown_blocks: 0 };
+        schedule_and_execute(&mut contract, &mut context, AdminAction::HarvestLimits { pool_id: 0, limits });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        contract.harvest(0, None);
+    }
+
+    #[test]
+    fn test_harvest_and_call_refund() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).block_timestamp(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+        schedule_and_execute(&mut contract, &mut context, AdminAction::PerformanceFee { fee_bps: 1000 });
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).block_index(100).build());
+        let reward = contract.get_account_reward(0, accounts(2));
+        let fee = reward / 10;
+        let amount = reward - fee;
+        contract.harvest_and_call(0, accounts(3), String::from("deposit"));
+        contract.assert_invariants();
+
+        // receiver chỉ dùng 1/4, phí chỉ tính trên phần đã dùng, phần còn lại cộng lại vào reward chưa trừ phí
+        let used = amount / 4;
+        let used_fee = (U256::from(fee) * U256::from(used) / U256::from(amount)).as_u128();
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&U128(used)).unwrap())]
+        );
+        assert_eq!(contract.ft_transfer_call_callback(0, U128(amount), accounts(2), U128(fee), U128(0)), U128(used));
+        contract.assert_invariants();
+        assert_eq!(contract.get_treasury().treasury_balance, U128(used_fee));
+        assert_eq!(contract.get_account_reward(0, accounts(2)), reward - used - used_fee);
+        assert_eq!(contract.internal_calculate_unpaid_reward(&contract.internal_get_pool(0)), reward - used - used_fee);
+    }
+
+    #[test]
+    fn test_process_scheduled_withdrawals() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        for index in 2..4 {
+            setup_staker(&mut contract, &mut context, accounts(index), 1000 * ONE_NEAR);
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(1).build());
+            contract.unstake(0, U128(100 * ONE_NEAR), None);
+            contract.assert_invariants();
+        }
+
+        // chỉ charlie đăng ký rút tự động
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.schedule_withdraw(0);
+        contract.assert_invariants();
+        assert!(contract.get_account_info(0, accounts(2)).withdraw_scheduled);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.process_withdrawals(0, vec![accounts(2), accounts(3)]);
+        contract.assert_invariants();
+
+        let account = contract.get_account_info(0, accounts(2));
+        assert_eq!(account.unstake_balance, U128(0));
+        assert!(!account.withdraw_scheduled);
+        assert_eq!(contract.get_account_info(0, accounts(3)).unstake_balance, U128(100 * ONE_NEAR));
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(100 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_unstake_and_schedule_withdraw() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake_and_schedule_withdraw(0, U128(100 * ONE_NEAR));
+        contract.assert_invariants();
+
+        let account = contract.get_account_info(0, accounts(2));
+        assert_eq!(account.stake_balance, U128(900 * ONE_NEAR));
+        assert_eq!(account.unstake_balance, U128(100 * ONE_NEAR));
+        assert!(account.withdraw_scheduled);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        contract.process_withdrawals(0, vec![accounts(2)]);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(2)).unstake_balance, U128(0));
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AMOUNT_MUST_LESS_THAN_BALANCE")]
+    fn test_unstake_and_schedule_withdraw_above_stake() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake_and_schedule_withdraw(0, U128(1001 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_scheduled_withdraw_rollback() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).epoch_height(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(100 * ONE_NEAR), None);
+        contract.assert_invariants();
+        contract.schedule_withdraw(0);
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).epoch_height(DEFAULT_UNBONDING_PERIOD).build());
+        let old_account = contract.internal_withdraw(0, accounts(2));
+        contract.assert_invariants();
+
+        // trong lúc chờ callback account stake thêm và unstake tiếp
+        testing_env!(context.predecessor_account_id(ft_contract()).build());
+        contract.ft_on_transfer(accounts(2), U128(500 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.unstake(0, U128(50 * ONE_NEAR), None);
+        contract.assert_invariants();
+
+        // transfer lỗi chỉ trả lại phần unstake đã rút, giữ nguyên stake mới
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_withdraw_callback(0, accounts(2), old_account);
+        contract.assert_invariants();
+
+        let account = contract.get_account_info(0, accounts(2));
+        assert_eq!(account.stake_balance, U128(1350 * ONE_NEAR));
+        assert_eq!(account.unstake_balance, U128(150 * ONE_NEAR));
+        assert!(account.withdraw_scheduled);
+        assert_eq!(contract.get_pool_info(0).total_unstake_balance, U128(150 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_operator_batch() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        for index in 2..4 {
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(ONE_NEAR / 100).block_index(0).build());
+            contract.storage_deposit(None, None);
+            contract.assert_invariants();
+            testing_env!(context.predecessor_account_id(accounts(index)).attached_deposit(1).build());
+            contract.approve_operator(accounts(4), vec![OperatorPermission::Compound, OperatorPermission::Harvest]);
+            contract.assert_invariants();
+        }
+        assert!(contract.is_approved(accounts(3), accounts(4), OperatorPermission::Harvest));
+
+        testing_env!(context.predecessor_account_id(ft_contract()).attached_deposit(0).build());
+        contract.ft_on_transfer(accounts(2), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+
+        // charlie vừa stake nên chưa có reward, bị bỏ qua
+        testing_env!(context.block_index(100).build());
+        contract.ft_on_transfer(accounts(3), U128(1000 * ONE_NEAR), String::new());
+        contract.assert_invariants();
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        let reward = contract.get_account_reward(0, accounts(2));
+        let results = contract.compound_many(0, vec![accounts(2), accounts(3), accounts(2)]);
+        contract.assert_invariants();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].success);
+        assert_eq!(results[0].amount, U128(reward));
+        assert!(!results[1].success);
+        assert_eq!(results[1].error, Some(String::from("ERR_REWARD_EQUAL_ZERO")));
+        assert_eq!(contract.get_account_info(0, accounts(2)).stake_balance, U128(1000 * ONE_NEAR + reward));
+
+        // account trùng chỉ harvest 1 lần, reward bị trừ ngay nên không harvest lại được khi chưa có callback
+        testing_env!(context.block_index(200).build());
+        let reward = contract.get_account_reward(0, accounts(3));
+        contract.harvest_many(0, vec![accounts(3), accounts(3)]);
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(3)), 0);
+
+        // transfer lỗi thì trả lại reward, kết quả giữ đúng thứ tự account truyền vào
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+        contract.ft_transfer_callback(0, U128(reward), accounts(3), U128(0), U128(0));
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_reward(0, accounts(3)), reward);
+
+        testing_env!(
+            context.predecessor_account_id(accounts(0)).attached_deposit(0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(serde_json::to_vec(&U128(reward)).unwrap())]
+        );
+        let results = contract.batch_callback(vec![
+            BatchResultJson::failed(accounts(2), "ERR_HARVEST_COOLDOWN"),
+            BatchResultJson::pending(accounts(3)),
+        ]);
+        contract.assert_invariants();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].account_id, accounts(2));
+        assert!(!results[0].success);
+        assert_eq!(results[1].account_id, accounts(3));
+        assert!(results[1].success);
+
+        // pool pause thì compound lỗi từng account thay vì lỗi cả batch
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+        contract.pause(0);
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        let results = contract.compound_many(0, vec![accounts(2), accounts(3)]);
+        contract.assert_invariants();
+        assert!(results.iter().all(|result| result.error == Some(String::from("ERR_CONTRACT_PAUSE"))));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_APPROVED")]
+    fn test_batch_without_approval() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).build());
+        contract.withdraw_many(0, vec![accounts(2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Required attached deposit of exactly 1 yoctoNEAR")]
+    fn test_batch_without_deposit() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(0).build());
+        contract.compound_many(0, vec![accounts(2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_GAS")]
+    fn test_batch_not_enough_gas() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        // 2 account cần 2 x (5 + 10 + 20) + 10 = 80 Tgas
+        testing_env!(context.predecessor_account_id(accounts(4)).attached_deposit(1).prepaid_gas(Gas(79_000_000_000_000)).build());
+        contract.harvest_many(0, vec![accounts(2), accounts(3)]);
+    }
+
+    #[test]
+    fn test_approved_operator() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.approve_operator(accounts(4), vec![OperatorPermission::Compound, OperatorPermission::Unstake, OperatorPermission::Compound]);
+        contract.assert_invariants();
+        assert_eq!(contract.get_approvals(accounts(2))[0].permissions, vec![OperatorPermission::Compound, OperatorPermission::Unstake]);
+        assert!(!contract.is_approved(accounts(2), accounts(4), OperatorPermission::Harvest));
+
+        testing_env!(context.predecessor_account_id(accounts(4)).block_index(100).build());
+        let reward = contract.get_account_reward(0, accounts(2));
+        assert_eq!(contract.compound(0, Some(accounts(2))), U128(reward));
+        contract.unstake(0, U128(ONE_NEAR), Some(accounts(2)));
+        contract.assert_invariants();
+        assert_eq!(contract.get_account_info(0, accounts(2)).unstake_balance, U128(ONE_NEAR));
+
+        // thu hồi quyền
+        testing_env!(context.predecessor_account_id(accounts(2)).build());
+        contract.approve_operator(accounts(4), vec![]);
+        contract.assert_invariants();
+        assert!(contract.get_approvals(accounts(2)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_APPROVED")]
+    fn test_harvest_without_approval() {
+        let mut context = get_context(false);
+        testing_env!(context.predecessor_account_id(accounts(1)).block_index(0).build());
+
+        let mut contract = StakingContract::new_default_config(accounts(1), ft_contract());
+
+        setup_staker(&mut contract, &mut context, accounts(2), 1000 * ONE_NEAR);
+
+        testing_env!(context.predecessor_account_id(accounts(2)).attached_deposit(1).build());
+        contract.approve_operator(accounts(4), vec![OperatorPermission::Compound]);
+
+        testing_env!(context.predecessor_account_id(accounts(4)).block_index(100).build());
+        contract.harvest(0, Some(accounts(2)));
+    }
+}
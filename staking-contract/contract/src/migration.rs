@@ -0,0 +1,188 @@
+use near_sdk::Gas;
+
+use crate::*;
+
+pub const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+pub const CURRENT_STATE_VERSION: u8 = 2;
+pub const GAS_FOR_UPGRADE: Gas = Gas(20_000_000_000_000);
+
+// State bản single pool (chưa có STATE_VERSION_KEY), accounts được đọc lại qua StorageKey::AccountKey khi migrate từng account
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StakingContractV1 {
+    pub owner_id: AccountId,
+    pub ft_contract_id: AccountId,
+    pub config: ConfigForReward,
+    pub total_stake_balance: Balance,
+    pub total_paid_reward_balance: Balance,
+    pub total_staker: Balance,
+    pub pre_reward: Balance,
+    pub last_block_balance_change: BlockHeight,
+    pub accounts: LookupMap<AccountId, UpgradableAccount>,
+    pub paused: bool,
+    pub pause_in_block: BlockHeight,
+}
+
+// Các version state của contract, version được lưu riêng ở STATE_VERSION_KEY.
+// Khi đổi layout StakingContract thì copy layout cũ thành variant mới và thêm nhánh chuyển đổi
+pub enum VersionedStakingContract {
+    V1(StakingContractV1),
+    Current(Box<StakingContract>),
+}
+
+impl VersionedStakingContract {
+    pub fn read() -> Self {
+        let state_version: Option<Vec<u8>> = env::storage_read(STATE_VERSION_KEY);
+
+        match state_version.as_deref() {
+            None => VersionedStakingContract::V1(env::state_read().expect("ERR_NOT_INITIALIZED")),
+            Some([CURRENT_STATE_VERSION]) => VersionedStakingContract::Current(Box::new(env::state_read().expect("ERR_NOT_INITIALIZED"))),
+            Some(_) => env::panic_str("ERR_UNKNOWN_STATE_VERSION"),
+        }
+    }
+}
+
+impl From<VersionedStakingContract> for StakingContract {
+    fn from(versioned_contract: VersionedStakingContract) -> Self {
+        match versioned_contract {
+            VersionedStakingContract::V1(old_contract) => {
+                // state single pool cũ thành pool 0
+                let mut contract = StakingContract {
+                    owner_id: old_contract.owner_id,
+                    pending_owner_id: None,
+                    roles: UnorderedMap::new(StorageKey::RoleKey),
+                    ft_contract_id: old_contract.ft_contract_id,
+                    staking_mode: StakingMode::FungibleToken,
+                    pools: Vector::new(StorageKey::PoolKey),
+                    accounts: LookupMap::new(StorageKey::PoolAccountKey),
+                    storage_accounts: LookupMap::new(StorageKey::StorageAccountKey),
+                    account_storage_usage: 0,
+                    timelock_delay: DEFAULT_TIMELOCK_DELAY,
+                    scheduled_actions: UnorderedMap::new(StorageKey::ScheduledActionKey),
+                    next_action_id: 0,
+                    fee_bps: 0,
+                    treasury_balance: 0,
+                    in_flight_outflow: 0,
+                    allowlist_enabled: false,
+                    allowlist: UnorderedSet::new(StorageKey::AllowlistKey),
+                    merkle_root: None,
+                    approvals: LookupMap::new(StorageKey::ApprovalKey),
+                };
+
+                let mut pool: Pool = Pool::new(0, old_contract.config);
+                pool.total_stake_balance = old_contract.total_stake_balance;
+                pool.total_paid_reward_balance = old_contract.total_paid_reward_balance;
+                pool.total_staker = old_contract.total_staker;
+                pool.pre_reward = old_contract.pre_reward;
+                pool.last_block_balance_change = old_contract.last_block_balance_change;
+                pool.loyalty_reserve_block = old_contract.last_block_balance_change;
+                pool.paused = old_contract.paused;
+                pool.pause_in_block = old_contract.pause_in_block;
+
+                contract.pools.push(&pool);
+
+                contract
+            },
+            VersionedStakingContract::Current(contract) => *contract,
+        }
+    }
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &[CURRENT_STATE_VERSION]);
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Deploy code mới (raw wasm trong input) rồi gọi migrate
+    pub fn upgrade(&self) -> Promise {
+        self.assert_role(Role::Upgrader);
+        let code: Vec<u8> = env::input().expect("ERR_NO_INPUT");
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call(
+                String::from("migrate"),
+                b"{}".to_vec(),
+                NO_DEPOSIT,
+                env::prepaid_gas() - env::used_gas() - GAS_FOR_UPGRADE
+            )
+    }
+
+    // Đọc state theo version và chuyển về layout hiện tại, account được migrate dần khi đọc (UpgradableAccount).
+    // Bản single pool không lưu tổng unstake balance nên phải truyền total_unstake_balance (tổng unstake_balance của các account cũ)
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(total_unstake_balance: Option<U128>) -> Self {
+        let versioned_contract: VersionedStakingContract = VersionedStakingContract::read();
+        let is_single_pool: bool = matches!(versioned_contract, VersionedStakingContract::V1(_));
+        let mut contract: StakingContract = StakingContract::from(versioned_contract);
+
+        if is_single_pool {
+            let mut pool: Pool = contract.internal_get_pool(0);
+            pool.total_unstake_balance = total_unstake_balance.expect("ERR_MISSING_TOTAL_UNSTAKE_BALANCE").0;
+            contract.pools.replace(0, &pool);
+        }
+
+        contract.internal_measure_account_storage_usage();
+        write_state_version();
+
+        contract
+    }
+
+    // Chuyển account của bản single pool vào pool 0
+    pub fn migrate_accounts(&mut self, account_ids: Vec<AccountId>) -> u32 {
+        self.assert_role(Role::Upgrader);
+
+        account_ids.iter()
+            .filter(|account_id| self.internal_migrate_old_account(account_id))
+            .count() as u32
+    }
+}
+
+impl StakingContract {
+
+    // Account của bản single pool chưa migrate được chuyển sang pool 0 ngay lần đầu dùng tới.
+    // Phải gọi trước khi đọc pool vì migrate ghi lại pool 0
+    pub(crate) fn internal_migrate_on_access(&mut self, pool_id: PoolId, account_id: &AccountId) {
+        if pool_id == 0 {
+            self.internal_migrate_old_account(account_id);
+        }
+    }
+
+    // Đọc account của bản single pool chưa migrate, dùng cho view
+    pub(crate) fn internal_get_old_account(&self, account_id: &AccountId) -> Option<Account> {
+        let old_accounts: LookupMap<AccountId, UpgradableAccount> = LookupMap::new(StorageKey::AccountKey);
+
+        old_accounts.get(account_id).map(Account::from)
+    }
+
+    // Account cũ đã trả storage cho contract nên phần storage mới do contract trả, trả về false nếu không có account cũ
+    pub(crate) fn internal_migrate_old_account(&mut self, account_id: &AccountId) -> bool {
+        let mut old_accounts: LookupMap<AccountId, UpgradableAccount> = LookupMap::new(StorageKey::AccountKey);
+        if self.accounts.contains_key(&(0, account_id.clone())) {
+            return false;
+        }
+        let account: Account = match old_accounts.remove(account_id) {
+            Some(upgradable_account) => Account::from(upgradable_account),
+            None => return false,
+        };
+
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        let mut pool: Pool = self.internal_get_pool(0);
+        let mut account_storage: AccountStorage = self.storage_accounts.get(account_id)
+            .unwrap_or(AccountStorage { deposit: 0, storage_usage: 0 });
+
+        pool.account_ids.insert(account_id);
+        self.pools.replace(0, &pool);
+        self.accounts.insert(&(0, account_id.clone()), &UpgradableAccount::from(account));
+        self.storage_accounts.insert(account_id, &account_storage);
+
+        let storage_usage: StorageUsage = env::storage_usage() - initial_storage_usage;
+        account_storage.storage_usage += storage_usage;
+        account_storage.deposit += env::storage_byte_cost() * Balance::from(storage_usage);
+        self.storage_accounts.insert(account_id, &account_storage);
+
+        true
+    }
+}
@@ -0,0 +1,281 @@
+use near_sdk::{Gas, PromiseOrValue, PromiseResult, serde_json};
+
+use crate::*;
+
+// mỗi account trong batch cần 1 transfer và 1 callback riêng, 6 account vẫn nằm trong giới hạn 300 Tgas
+pub const MAX_BATCH_SIZE: usize = 6;
+pub const BATCH_CALLBACK_GAS: Gas = Gas(10_000_000_000_000);
+// gas xử lý mỗi account trong batch, chưa tính transfer và callback của account đó
+pub const BATCH_ACCOUNT_GAS: Gas = Gas(5_000_000_000_000);
+pub const MAX_OPERATORS_PER_ACCOUNT: usize = 10;
+
+// Các thao tác operator được approve làm thay account, áp dụng cho tất cả các pool
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum OperatorPermission {
+    Compound,
+    Harvest, // reward vẫn trả về chính account
+    Unstake, // gồm cả withdraw unstake balance về account
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct OperatorApproval {
+    pub operator_id: AccountId,
+    pub permissions: Vec<OperatorPermission>,
+}
+
+// Kết quả của từng account trong batch, account bị bỏ qua hoặc transfer lỗi có success = false và lỗi trong error
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchResultJson {
+    pub account_id: AccountId,
+    pub success: bool,
+    pub amount: U128,
+    pub error: Option<String>,
+}
+
+impl BatchResultJson {
+    pub fn failed(account_id: AccountId, error: &str) -> Self {
+        BatchResultJson { account_id, success: false, amount: U128(0), error: Some(error.to_string()) }
+    }
+
+    // account đang chờ transfer, kết quả được điền trong batch_callback
+    pub fn pending(account_id: AccountId) -> Self {
+        BatchResultJson { account_id, success: false, amount: U128(0), error: None }
+    }
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Cho operator làm thay các thao tác trong permissions, permissions rỗng để thu hồi.
+    // Storage của approval được trừ vào storage deposit của account
+    #[payable]
+    pub fn approve_operator(&mut self, operator_id: AccountId, permissions: Vec<OperatorPermission>) {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        assert!(self.storage_accounts.contains_key(&account_id), "ERR_ACCOUNT_NOT_FOUND");
+        assert_ne!(account_id, operator_id, "ERR_INVALID_OPERATOR");
+
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        let mut approvals: Vec<OperatorApproval> = self.approvals.get(&account_id).unwrap_or_default();
+        approvals.retain(|approval| approval.operator_id != operator_id);
+
+        let permissions: Vec<OperatorPermission> = permissions.into_iter().fold(Vec::new(), |mut unique_permissions, permission| {
+            if !unique_permissions.contains(&permission) {
+                unique_permissions.push(permission);
+            }
+            unique_permissions
+        });
+        if !permissions.is_empty() {
+            approvals.push(OperatorApproval { operator_id: operator_id.clone(), permissions: permissions.clone() });
+            assert!(approvals.len() <= MAX_OPERATORS_PER_ACCOUNT, "ERR_TOO_MANY_OPERATORS");
+        }
+
+        if approvals.is_empty() {
+            self.approvals.remove(&account_id);
+        } else {
+            self.approvals.insert(&account_id, &approvals);
+        }
+        self.internal_update_storage_usage(&account_id, initial_storage_usage);
+
+        EventLogVariant::ApproveOperator(vec![ApprovalLog { account_id, operator_id, permissions }]).emit();
+    }
+
+    pub fn get_approvals(&self, account_id: AccountId) -> Vec<OperatorApproval> {
+        self.approvals.get(&account_id).unwrap_or_default()
+    }
+
+    pub fn is_approved(&self, account_id: AccountId, operator_id: AccountId, permission: OperatorPermission) -> bool {
+        self.internal_is_approved(&account_id, &operator_id, permission)
+    }
+
+    // Reward luôn trả về chính account, không dùng được khi pool bật vesting
+    #[payable]
+    pub fn harvest_many(&mut self, pool_id: PoolId, account_ids: Vec<AccountId>) -> PromiseOrValue<Vec<BatchResultJson>> {
+        assert_one_yocto();
+        let account_ids: Vec<AccountId> = Self::internal_unique_account_ids(account_ids);
+        Self::internal_assert_batch_gas(account_ids.len(), Gas(FT_TRANSFER_GAS.0 + HARVEST_CALLBACK_GAS.0));
+        let pool: Pool = self.internal_get_pool(pool_id);
+
+        let mut promise: Option<Promise> = None;
+        let mut results: Vec<BatchResultJson> = Vec::new();
+
+        for account_id in account_ids {
+            self.internal_migrate_on_access(pool_id, &account_id);
+            let account: Account = self.internal_get_approved_account(pool_id, &account_id, OperatorPermission::Harvest);
+            let (amount, _) = self.internal_calculate_harvest_amount(&pool, &account);
+            let check: Result<(), &'static str> = match pool.vesting {
+                Some(_) => Err("ERR_POOL_VESTING_ENABLED"),
+                None => self.internal_check_harvest(&pool, &account, amount),
+            };
+            if let Err(err) = check {
+                results.push(BatchResultJson::failed(account_id, err));
+                continue;
+            }
+
+            match self.internal_harvest(pool_id, account_id.clone(), account_id.clone(), None) {
+                PromiseOrValue::Promise(harvest_promise) => promise = Some(Self::internal_join_promise(promise, harvest_promise)),
+                PromiseOrValue::Value(_) => unreachable!(),
+            }
+            results.push(BatchResultJson::pending(account_id));
+        }
+
+        self.internal_batch_result(promise, results)
+    }
+
+    // Pool đang pause hoặc stake bằng NEAR thì trả lỗi cho từng account
+    #[payable]
+    pub fn compound_many(&mut self, pool_id: PoolId, account_ids: Vec<AccountId>) -> Vec<BatchResultJson> {
+        assert_one_yocto();
+        let account_ids: Vec<AccountId> = Self::internal_unique_account_ids(account_ids);
+
+        account_ids.into_iter()
+            .map(|account_id| {
+                self.internal_migrate_on_access(pool_id, &account_id);
+                let pool: Pool = self.internal_get_pool(pool_id);
+                let account: Account = self.internal_get_approved_account(pool_id, &account_id, OperatorPermission::Compound);
+                if let Err(err) = self.internal_check_compound(&pool) {
+                    return BatchResultJson::failed(account_id, err);
+                }
+
+                let (amount, _) = self.internal_calculate_harvest_amount(&pool, &account);
+                if amount == 0 {
+                    return BatchResultJson::failed(account_id, "ERR_REWARD_EQUAL_ZERO");
+                }
+
+                let amount: Balance = self.internal_compound(pool_id, account_id.clone());
+                BatchResultJson { account_id, success: true, amount: U128(amount), error: None }
+            })
+            .collect()
+    }
+
+    // Rút unstake balance đã hết unbonding về từng account
+    #[payable]
+    pub fn withdraw_many(&mut self, pool_id: PoolId, account_ids: Vec<AccountId>) -> PromiseOrValue<Vec<BatchResultJson>> {
+        assert_one_yocto();
+        let account_ids: Vec<AccountId> = Self::internal_unique_account_ids(account_ids);
+        Self::internal_assert_batch_gas(account_ids.len(), Gas(FT_TRANSFER_GAS.0 + WITHDRAW_CALLBACK_GAS.0));
+
+        let mut promise: Option<Promise> = None;
+        let mut results: Vec<BatchResultJson> = Vec::new();
+
+        for account_id in account_ids {
+            self.internal_migrate_on_access(pool_id, &account_id);
+            let account: Account = self.internal_get_approved_account(pool_id, &account_id, OperatorPermission::Unstake);
+            if account.unstake_balance == 0 {
+                results.push(BatchResultJson::failed(account_id, "ERR_UNSTAKE_BALANCE_IS_ZERO"));
+                continue;
+            }
+            if account.unstake_available_epoch > env::epoch_height() {
+                results.push(BatchResultJson::failed(account_id, "ERR_DISABLE_WITHDRAW"));
+                continue;
+            }
+
+            let old_account: Account = self.internal_withdraw(pool_id, account_id.clone());
+            let withdraw_promise: Promise = self.internal_withdraw_transfer(pool_id, account_id.clone(), old_account);
+            promise = Some(Self::internal_join_promise(promise, withdraw_promise));
+            results.push(BatchResultJson::pending(account_id));
+        }
+
+        self.internal_batch_result(promise, results)
+    }
+
+    // Callback của từng account đang chờ transfer trả về số token đã transfer, 0 là transfer lỗi và đã rollback.
+    // Kết quả giữ đúng thứ tự account_ids truyền vào batch
+    #[private]
+    pub fn batch_callback(&self, results: Vec<BatchResultJson>) -> Vec<BatchResultJson> {
+        let pending_count: usize = results.iter().filter(|result| result.error.is_none()).count();
+        assert_eq!(env::promise_results_count(), pending_count as u64, "ERR_TOO_MANY_RESULTS");
+
+        let mut promise_index: u64 = 0;
+        results.into_iter()
+            .map(|result| {
+                if result.error.is_some() {
+                    return result;
+                }
+
+                let amount: U128 = match env::promise_result(promise_index) {
+                    PromiseResult::Successful(value) => serde_json::from_slice(&value).unwrap_or(U128(0)),
+                    _ => U128(0),
+                };
+                promise_index += 1;
+
+                if amount.0 > 0 {
+                    BatchResultJson { account_id: result.account_id, success: true, amount, error: None }
+                } else {
+                    BatchResultJson::failed(result.account_id, "ERR_TRANSFER_FAILED")
+                }
+            })
+            .collect()
+    }
+}
+
+impl StakingContract {
+
+    // Account gọi phải được approve permission tương ứng cho từng account, lỗi cả batch nếu có account không cho phép
+    pub(crate) fn internal_get_approved_account(&self, pool_id: PoolId, account_id: &AccountId, permission: OperatorPermission) -> Account {
+        let account_id: AccountId = self.internal_resolve_account_id(Some(account_id.clone()), permission);
+
+        self.internal_get_account(pool_id, &account_id)
+    }
+
+    pub(crate) fn internal_is_approved(&self, account_id: &AccountId, operator_id: &AccountId, permission: OperatorPermission) -> bool {
+        self.approvals.get(account_id).is_some_and(|approvals| approvals.iter()
+            .any(|approval| approval.operator_id == *operator_id && approval.permissions.contains(&permission)))
+    }
+
+    // account_id để trống hoặc trùng account gọi thì thao tác trên chính account gọi, ngược lại account gọi phải được approve
+    pub(crate) fn internal_resolve_account_id(&self, account_id: Option<AccountId>, permission: OperatorPermission) -> AccountId {
+        let predecessor_account_id: AccountId = env::predecessor_account_id();
+
+        match account_id {
+            Some(account_id) if account_id != predecessor_account_id => {
+                assert!(self.internal_is_approved(&account_id, &predecessor_account_id, permission), "ERR_NOT_APPROVED");
+                account_id
+            },
+            _ => predecessor_account_id,
+        }
+    }
+
+    fn internal_join_promise(promise: Option<Promise>, next_promise: Promise) -> Promise {
+        match promise {
+            Some(promise) => promise.and(next_promise),
+            None => next_promise,
+        }
+    }
+
+    // Bỏ account trùng, giữ thứ tự lần xuất hiện đầu tiên
+    fn internal_unique_account_ids(account_ids: Vec<AccountId>) -> Vec<AccountId> {
+        let account_ids: Vec<AccountId> = account_ids.into_iter().fold(Vec::new(), |mut unique_account_ids, account_id| {
+            if !unique_account_ids.contains(&account_id) {
+                unique_account_ids.push(account_id);
+            }
+            unique_account_ids
+        });
+        assert!(account_ids.len() <= MAX_BATCH_SIZE, "ERR_TOO_MANY_ACCOUNTS");
+
+        account_ids
+    }
+
+    // Kiểm tra trước khi xử lý account nào để batch không hết gas giữa chừng, transfer_gas là gas transfer và callback của mỗi account
+    fn internal_assert_batch_gas(account_count: usize, transfer_gas: Gas) {
+        let required_gas: u64 = (BATCH_ACCOUNT_GAS.0 + transfer_gas.0) * account_count as u64 + BATCH_CALLBACK_GAS.0;
+
+        assert!(env::prepaid_gas().0 >= required_gas, "ERR_NOT_ENOUGH_GAS");
+    }
+
+    fn internal_batch_result(&self, promise: Option<Promise>, results: Vec<BatchResultJson>) -> PromiseOrValue<Vec<BatchResultJson>> {
+        match promise {
+            Some(promise) => promise.then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(BATCH_CALLBACK_GAS)
+                .batch_callback(results)
+            ).into(),
+            None => PromiseOrValue::Value(results),
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use near_sdk::collections::{UnorderedSet, Vector};
+
+use crate::*;
+
+pub type PoolId = u32;
+
+// số epoch phải chờ từ lúc unstake đến lúc withdraw được
+pub const DEFAULT_UNBONDING_PERIOD: EpochHeight = 1;
+// số checkpoint tối đa giữ cho mỗi pool, vượt quá thì gộp 2 checkpoint cũ nhất
+pub const MAX_REWARD_CHECKPOINTS: u64 = 16;
+
+// Config của một đoạn reward block đã kết thúc, account chưa chốt reward vẫn tính đoạn này theo config cũ
+#[derive(BorshDeserialize, BorshSerialize, Clone, Copy)]
+pub struct RewardCheckpoint {
+    pub end_block: BlockHeight,
+    pub config: ConfigForReward,
+    pub loyalty: LoyaltyConfig,
+}
+
+impl RewardCheckpoint {
+    // Gộp với đoạn ngay sau, lấy rate và loyalty thấp hơn để reward của account chưa chốt không vượt quá reward pool đã chốt
+    pub fn merge(&self, next: &RewardCheckpoint) -> RewardCheckpoint {
+        let config: ConfigForReward = if self.config.reward_numerator as u128 * next.config.reward_denumerator as u128
+            <= next.config.reward_numerator as u128 * self.config.reward_denumerator as u128 {
+            self.config
+        } else {
+            next.config
+        };
+
+        RewardCheckpoint {
+            end_block: next.end_block,
+            config,
+            loyalty: LoyaltyConfig {
+                max_multiplier_bps: self.loyalty.max_multiplier_bps.min(next.loyalty.max_multiplier_bps),
+                blocks_to_max: self.loyalty.blocks_to_max.max(next.loyalty.blocks_to_max),
+            },
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Pool {
+    pub config: ConfigForReward, // cấu hình công thức trả thưởng riêng của pool
+    pub total_stake_balance: Balance,
+    pub total_unstake_balance: Balance, // tổng unstake balance của các account, chưa withdraw
+    pub total_paid_reward_balance: Balance,
+    pub total_funded_reward_balance: Balance, // tổng ft token reward funder đã nạp để trả reward
+    pub total_referral_reward_balance: Balance, // tổng reward giới thiệu đã cộng cho các account
+    pub total_loyalty_bonus_balance: Balance, // tổng reward loyalty đã chốt cho các account, ngoài pre_reward của pool
+    pub total_loyalty_reserve_balance: Balance, // reward loyalty tối đa các account có thể nhận cho phần chưa chốt
+    pub loyalty_reserve_per_share: u128, // reward loyalty tối đa cộng dồn trên mỗi đơn vị stake, nhân LOYALTY_RESERVE_PRECISION
+    pub loyalty_reserve_block: BlockHeight, // reward block lần cuối cộng total_loyalty_reserve_balance
+    pub total_penalty_reward_balance: Balance, // tổng phí claim sớm đã chia cho các staker
+    pub total_vesting_balance: Balance, // reward đang nằm trong lịch vesting, chưa claim
+    pub penalty_per_share: u128, // phí claim sớm cộng dồn trên mỗi đơn vị stake, nhân PENALTY_PRECISION
+    pub total_staker: Balance,
+    pub pre_reward: Balance,
+    pub last_block_balance_change: BlockHeight,
+    pub paused: bool, // nếu hết token không thể trả cho user, pause pool, user sẽ không deposit thêm và reward cũng không trả thêm nữa
+    pub pause_in_block: BlockHeight,
+    pub total_paused_blocks: BlockHeight, // tổng số block pool bị pause, không tính reward cho các block này
+    pub account_ids: UnorderedSet<AccountId>, // danh sách account của pool, dùng để phân trang
+    pub unbonding_period: EpochHeight, // số epoch chờ sau khi unstake, chỉ đổi được qua timelock
+    pub limits: StakeLimits,
+    pub referral_bps: u32, // phần trăm (basis point) reward của account được cộng thêm cho người giới thiệu
+    pub loyalty: LoyaltyConfig,
+    pub vesting: Option<VestingConfig>, // None thì harvest transfer reward ngay
+    pub harvest_limits: HarvestLimits,
+    pub reward_checkpoints: Vector<RewardCheckpoint>, // các đoạn config cũ theo thứ tự end_block, tối đa MAX_REWARD_CHECKPOINTS
+}
+
+impl Pool {
+    pub fn new(pool_id: PoolId, config: ConfigForReward) -> Self {
+        Pool {
+            config,
+            total_stake_balance: 0,
+            total_unstake_balance: 0,
+            total_paid_reward_balance: 0,
+            total_funded_reward_balance: 0,
+            total_referral_reward_balance: 0,
+            total_loyalty_bonus_balance: 0,
+            total_loyalty_reserve_balance: 0,
+            loyalty_reserve_per_share: 0,
+            loyalty_reserve_block: env::block_height(),
+            total_penalty_reward_balance: 0,
+            total_vesting_balance: 0,
+            penalty_per_share: 0,
+            total_staker: 0,
+            pre_reward: 0,
+            last_block_balance_change: env::block_height(),
+            paused: false,
+            pause_in_block: 0,
+            total_paused_blocks: 0,
+            account_ids: UnorderedSet::new(StorageKey::PoolAccountIdsKey { pool_id }),
+            unbonding_period: DEFAULT_UNBONDING_PERIOD,
+            limits: StakeLimits::default(),
+            referral_bps: 0,
+            loyalty: LoyaltyConfig::default(),
+            vesting: None,
+            harvest_limits: HarvestLimits::default(),
+            reward_checkpoints: Vector::new(StorageKey::RewardCheckpointKey { pool_id }),
+        }
+    }
+
+    // Block dùng để tính reward: dừng lại khi pause, tiếp tục từ chỗ cũ khi resume
+    pub fn reward_block(&self) -> BlockHeight {
+        if self.paused {
+            self.pause_in_block - self.total_paused_blocks
+        } else {
+            env::block_height() - self.total_paused_blocks
+        }
+    }
+
+    pub fn pause(&mut self) {
+        assert!(!self.paused, "ERR_POOL_ALREADY_PAUSED");
+
+        self.paused = true;
+        self.pause_in_block = env::block_height();
+    }
+
+    pub fn resume(&mut self) {
+        assert!(self.paused, "ERR_POOL_NOT_PAUSED");
+
+        self.total_paused_blocks += env::block_height() - self.pause_in_block;
+        self.paused = false;
+        self.pause_in_block = 0;
+    }
+}
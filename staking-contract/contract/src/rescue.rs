@@ -0,0 +1,85 @@
+use near_sdk::{Gas, PromiseResult, serde_json};
+
+use crate::*;
+
+pub const FT_BALANCE_OF_GAS: Gas = Gas(5_000_000_000_000);
+pub const RESCUE_CALLBACK_GAS: Gas = Gas(20_000_000_000_000);
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Rút token gửi nhầm vào contract. Với ft token của pool chỉ rút được phần dư sau khi trừ principal và reward đã cam kết
+    pub fn rescue_tokens(&mut self, token_id: AccountId, amount: U128, receiver_id: AccountId) -> Promise {
+        self.assert_owner();
+        assert!(amount.0 > 0, "ERR_AMOUNT_EQUAL_ZERO");
+
+        if token_id != self.ft_contract_id {
+            return self.internal_rescue_transfer(token_id, amount, receiver_id);
+        }
+
+        // số dư thực tế phải lấy từ ft contract, phần dư được tính lại trong callback
+        ext_ft_contract::ext(token_id.clone())
+            .with_static_gas(FT_BALANCE_OF_GAS)
+            .ft_balance_of(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(RESCUE_CALLBACK_GAS)
+                .rescue_tokens_callback(token_id, amount, receiver_id)
+            )
+    }
+
+    #[private]
+    pub fn rescue_tokens_callback(&mut self, token_id: AccountId, amount: U128, receiver_id: AccountId) -> Promise {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let balance: U128 = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice(&value).expect("ERR_INVALID_BALANCE"),
+            _ => env::panic_str("ERR_GET_BALANCE_FAILED"),
+        };
+
+        let surplus: Balance = balance.0.saturating_sub(self.internal_committed_ft_balance());
+        assert!(amount.0 <= surplus, "ERR_AMOUNT_GREATER_THAN_SURPLUS");
+
+        self.internal_rescue_transfer(token_id, amount, receiver_id)
+    }
+}
+
+impl StakingContract {
+
+    // Số ft token contract phải giữ: principal (nếu stake bằng ft token), reward và reward đang vest của tất cả các pool,
+    // phí trong treasury và token đang transfer
+    pub(crate) fn internal_committed_ft_balance(&self) -> Balance {
+        self.treasury_balance + self.in_flight_outflow + self.pools.iter()
+            .map(|pool| {
+                let principal: Balance = self.internal_ft_principal(pool.total_stake_balance + pool.total_unstake_balance);
+
+                // quỹ reward còn lại dành cho cả reward về sau nên lấy phần lớn hơn
+                let accrued_reward: Balance = self.internal_calculate_unpaid_reward(&pool);
+                let reward_reserve: Balance = pool.total_funded_reward_balance.saturating_sub(pool.total_paid_reward_balance);
+
+                principal + pool.total_vesting_balance + accrued_reward.max(reward_reserve)
+            })
+            .sum::<Balance>()
+    }
+
+    // principal chỉ là ft token khi stake bằng ft token
+    pub(crate) fn internal_ft_principal(&self, amount: Balance) -> Balance {
+        match self.staking_mode {
+            StakingMode::FungibleToken => amount,
+            StakingMode::Native => 0,
+        }
+    }
+
+    fn internal_rescue_transfer(&self, token_id: AccountId, amount: U128, receiver_id: AccountId) -> Promise {
+        EventLogVariant::RescueTokens(vec![RescueLog {
+            token_id: token_id.clone(),
+            receiver_id: receiver_id.clone(),
+            amount,
+        }]).emit();
+
+        ext_ft_contract::ext(token_id)
+            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+            .with_static_gas(FT_TRANSFER_GAS)
+            .ft_transfer(receiver_id, amount, Some(String::from("Staking contract rescue tokens")))
+    }
+}
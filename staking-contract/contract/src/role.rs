@@ -0,0 +1,99 @@
+use crate::*;
+
+// Quyền admin được owner cấp cho account khác, owner luôn có tất cả các quyền
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+    Pauser, // pause / resume pool
+    ConfigManager, // tạo pool, đổi config reward
+    RewardFunder, // nạp ft token vào quỹ reward của pool
+    Upgrader, // deploy code mới và migrate account
+    Treasurer, // rút phí trong treasury
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccountRolesJson {
+    pub account_id: AccountId,
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RolesJson {
+    pub owner_id: AccountId,
+    pub pending_owner_id: Option<AccountId>,
+    pub accounts: Vec<AccountRolesJson>,
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Bước 1 chuyển owner: owner hiện tại đề cử, owner mới phải gọi accept_owner
+    pub fn propose_owner(&mut self, new_owner_id: AccountId) {
+        self.assert_owner();
+
+        self.pending_owner_id = Some(new_owner_id.clone());
+
+        EventLogVariant::ProposeOwner(vec![OwnerLog { owner_id: new_owner_id }]).emit();
+    }
+
+    pub fn accept_owner(&mut self) {
+        let account_id: AccountId = env::predecessor_account_id();
+        assert_eq!(self.pending_owner_id.as_ref(), Some(&account_id), "ERR_NOT_PENDING_OWNER");
+
+        self.owner_id = account_id.clone();
+        self.pending_owner_id = None;
+
+        EventLogVariant::AcceptOwner(vec![OwnerLog { owner_id: account_id }]).emit();
+    }
+
+    pub fn grant_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles: Vec<Role> = self.roles.get(&account_id).unwrap_or_default();
+
+        if !roles.contains(&role) {
+            roles.push(role);
+            self.roles.insert(&account_id, &roles);
+
+            EventLogVariant::GrantRole(vec![RoleLog { account_id, role }]).emit();
+        }
+    }
+
+    pub fn revoke_role(&mut self, account_id: AccountId, role: Role) {
+        self.assert_owner();
+        let mut roles: Vec<Role> = self.roles.get(&account_id).unwrap_or_default();
+
+        if roles.contains(&role) {
+            roles.retain(|r| *r != role);
+            if roles.is_empty() {
+                self.roles.remove(&account_id);
+            } else {
+                self.roles.insert(&account_id, &roles);
+            }
+
+            EventLogVariant::RevokeRole(vec![RoleLog { account_id, role }]).emit();
+        }
+    }
+
+    pub fn get_roles(&self) -> RolesJson {
+        RolesJson {
+            owner_id: self.owner_id.clone(),
+            pending_owner_id: self.pending_owner_id.clone(),
+            accounts: self.roles.iter()
+                .map(|(account_id, roles)| AccountRolesJson { account_id, roles })
+                .collect(),
+        }
+    }
+
+    pub fn has_role(&self, account_id: AccountId, role: Role) -> bool {
+        self.internal_has_role(&account_id, role)
+    }
+}
+
+impl StakingContract {
+
+    pub(crate) fn internal_has_role(&self, account_id: &AccountId, role: Role) -> bool {
+        *account_id == self.owner_id || self.roles.get(account_id).is_some_and(|roles| roles.contains(&role))
+    }
+}
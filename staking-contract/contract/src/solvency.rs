@@ -0,0 +1,85 @@
+use near_sdk::{Gas, PromiseResult, serde_json};
+
+use crate::*;
+
+pub const SOLVENCY_CALLBACK_GAS: Gas = Gas(10_000_000_000_000);
+
+// So sánh số ft token contract đang giữ với số phải trả cho user
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SolvencyReport {
+    pub ft_balance: U128,
+    pub total_stake_balance: U128, // chỉ tính khi stake bằng ft token
+    pub total_unstake_balance: U128, // chỉ tính khi stake bằng ft token
+    pub total_unpaid_reward: U128,
+    pub total_vesting_balance: U128,
+    pub treasury_balance: U128,
+    pub in_flight_outflow: U128, // token đang transfer, chưa có callback
+    pub total_liabilities: U128, // tính giống phần rescue_tokens phải giữ lại, gồm cả quỹ reward đã nạp chưa trả
+    pub surplus: U128,
+    pub deficit: U128,
+    pub is_solvent: bool,
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Cần gọi ft_balance_of nên không dùng được view call, gọi như change method không cần deposit
+    pub fn get_solvency_report(&self) -> Promise {
+        ext_ft_contract::ext(self.ft_contract_id.clone())
+            .with_static_gas(FT_BALANCE_OF_GAS)
+            .ft_balance_of(env::current_account_id())
+            .then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(SOLVENCY_CALLBACK_GAS)
+                .solvency_report_callback()
+            )
+    }
+
+    #[private]
+    pub fn solvency_report_callback(&self) -> SolvencyReport {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let ft_balance: U128 = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice(&value).expect("ERR_INVALID_BALANCE"),
+            _ => env::panic_str("ERR_GET_BALANCE_FAILED"),
+        };
+
+        self.internal_solvency_report(ft_balance.0)
+    }
+}
+
+impl StakingContract {
+
+    pub(crate) fn internal_solvency_report(&self, ft_balance: Balance) -> SolvencyReport {
+        let mut total_stake_balance: Balance = 0;
+        let mut total_unstake_balance: Balance = 0;
+        let mut total_unpaid_reward: Balance = 0;
+        let mut total_vesting_balance: Balance = 0;
+
+        for pool in self.pools.iter() {
+            if self.staking_mode == StakingMode::FungibleToken {
+                total_stake_balance += pool.total_stake_balance;
+                total_unstake_balance += pool.total_unstake_balance;
+            }
+            total_unpaid_reward += self.internal_calculate_unpaid_reward(&pool);
+            total_vesting_balance += pool.total_vesting_balance;
+        }
+
+        let total_liabilities: Balance = self.internal_committed_ft_balance();
+
+        SolvencyReport {
+            ft_balance: U128(ft_balance),
+            total_stake_balance: U128(total_stake_balance),
+            total_unstake_balance: U128(total_unstake_balance),
+            total_unpaid_reward: U128(total_unpaid_reward),
+            total_vesting_balance: U128(total_vesting_balance),
+            treasury_balance: U128(self.treasury_balance),
+            in_flight_outflow: U128(self.in_flight_outflow),
+            total_liabilities: U128(total_liabilities),
+            surplus: U128(ft_balance.saturating_sub(total_liabilities)),
+            deficit: U128(total_liabilities.saturating_sub(ft_balance)),
+            is_solvent: ft_balance >= total_liabilities,
+        }
+    }
+}
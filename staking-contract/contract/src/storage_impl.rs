@@ -0,0 +1,324 @@
+use near_sdk::PromiseResult;
+
+use crate::*;
+
+// độ dài account id lớn nhất, dùng để đo storage tối thiểu cho 1 account
+const MAX_ACCOUNT_ID_LENGTH: usize = 64;
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+// Số NEAR user đã deposit cho storage và số byte storage account đang chiếm (gồm cả account ở các pool)
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountStorage {
+    pub deposit: Balance,
+    pub storage_usage: StorageUsage,
+}
+
+impl AccountStorage {
+    pub fn storage_cost(&self) -> Balance {
+        env::storage_byte_cost() * Balance::from(self.storage_usage)
+    }
+
+    pub fn available(&self) -> Balance {
+        self.deposit.saturating_sub(self.storage_cost())
+    }
+
+    pub fn to_storage_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.deposit),
+            available: U128(self.available()),
+        }
+    }
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // để 1 fn deposit được thì sẽ phải có macro này
+    #[payable]
+    pub fn storage_deposit(&mut self, account_id: Option<AccountId>, registration_only: Option<bool>) -> StorageBalance {
+        let amount: Balance = env::attached_deposit();
+        let account_id: AccountId = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only: bool = registration_only.unwrap_or(false);
+        // account của bản single pool giữ nguyên đăng ký storage, contract trả phần storage khi migrate
+        self.internal_migrate_on_access(0, &account_id);
+
+        if let Some(mut account_storage) = self.storage_accounts.get(&account_id) {
+            if registration_only {
+                // account đã đăng ký, refund toàn bộ token deposit
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+            } else {
+                account_storage.deposit += amount;
+                self.storage_accounts.insert(&account_id, &account_storage);
+            }
+
+            return account_storage.to_storage_balance();
+        }
+
+        // đăng ký storage không kiểm tra được merkle proof, account ngoài merkle allowlist vẫn bị chặn lúc stake
+        if self.merkle_root.is_none() {
+            self.assert_allowlisted(&account_id);
+        }
+        let min_balance: Balance = self.storage_balance_bounds().min.0;
+        assert!(amount >= min_balance, "ERR_DEPOSIT_LESS_THAN_MIN_STORAGE");
+
+        let deposit: Balance = if registration_only { min_balance } else { amount };
+        self.internal_register_storage(&account_id, deposit);
+
+        EventLogVariant::Register(vec![StorageLog { account_id: account_id.clone(), amount: U128(deposit) }]).emit();
+
+        // Refund lại token deposit còn thừa
+        let refund: Balance = amount - deposit;
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.storage_accounts.get(&account_id).unwrap().to_storage_balance()
+    }
+
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        self.internal_migrate_on_access(0, &account_id);
+        let mut account_storage: AccountStorage = self.storage_accounts.get(&account_id).expect("ERR_NOT_REGISTERED");
+
+        let available: Balance = account_storage.available();
+        let amount: Balance = amount.map(|amount| amount.0).unwrap_or(available);
+        assert!(amount <= available, "ERR_AMOUNT_GREATER_THAN_AVAILABLE_STORAGE");
+
+        account_storage.deposit -= amount;
+        self.storage_accounts.insert(&account_id, &account_storage);
+
+        if amount > 0 {
+            Promise::new(account_id).transfer(amount);
+        }
+
+        account_storage.to_storage_balance()
+    }
+
+    // force = true: trả reward và unstake balance đã hết unbonding của tất cả các pool rồi mới xoá account,
+    // stake vẫn phải unstake và chờ unbonding như bình thường
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        let force: bool = force.unwrap_or(false);
+        self.internal_migrate_on_access(0, &account_id);
+
+        if self.storage_accounts.get(&account_id).is_none() {
+            return false;
+        }
+
+        let mut settled_accounts: Vec<(PoolId, Account)> = Vec::new();
+        for pool_id in 0..self.pools.len() as PoolId {
+            if let Some(upgradable_account) = self.accounts.get(&(pool_id, account_id.clone())) {
+                let account: Account = Account::from(upgradable_account);
+                assert!(force || (account.stake_balance == 0 && account.unstake_balance == 0), "ERR_ACCOUNT_HAS_BALANCE");
+                assert_eq!(account.stake_balance, 0, "ERR_ACCOUNT_HAS_STAKE");
+                assert!(account.unstake_balance == 0 || account.unstake_available_epoch <= env::epoch_height(), "ERR_DISABLE_WITHDRAW");
+                // reward đang vest phải claim_vested trước, force cũng không bỏ qua lịch vesting
+                assert_eq!(account.vesting.balance(), 0, "ERR_ACCOUNT_HAS_VESTING");
+                // callback của withdraw / harvest / claim_vested còn ghi lại vào account
+                assert_eq!(account.pending_transfer_count, 0, "ERR_ACCOUNT_HAS_PENDING_TRANSFER");
+
+                settled_accounts.push((pool_id, self.internal_remove_account(pool_id, &account_id)));
+            }
+        }
+
+        self.approvals.remove(&account_id);
+        let account_storage: AccountStorage = self.storage_accounts.remove(&account_id).unwrap();
+        EventLogVariant::Unregister(vec![StorageLog { account_id: account_id.clone(), amount: U128(account_storage.deposit) }]).emit();
+
+        let principal: Balance = settled_accounts.iter().map(|(_, account)| account.stake_balance + account.unstake_balance).sum();
+        let reward: Balance = settled_accounts.iter().map(|(_, account)| account.pre_reward + account.referral_reward).sum();
+
+        // stake bằng NEAR thì trả principal ngay, ft token chỉ còn reward
+        let ft_amount: Balance = match self.staking_mode {
+            StakingMode::FungibleToken => principal + reward,
+            StakingMode::Native => {
+                if principal > 0 {
+                    Promise::new(account_id.clone()).transfer(principal);
+                }
+                reward
+            },
+        };
+
+        if ft_amount == 0 {
+            if account_storage.deposit > 0 {
+                Promise::new(account_id).transfer(account_storage.deposit);
+            }
+            return true;
+        }
+
+        // storage deposit chỉ refund sau khi ft_transfer thành công.
+        // principal đã trừ khỏi pool nên tính là đang transfer, reward chỉ tính là đã trả trong callback
+        self.in_flight_outflow += ft_amount - reward;
+
+        ext_ft_contract::ext(self.ft_contract_id.clone())
+            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+            .with_static_gas(FT_TRANSFER_GAS)
+            .ft_transfer(
+                account_id.clone(),
+                U128(ft_amount),
+                Some(String::from("Staking contract unregister"))
+            ).then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(UNREGISTER_CALLBACK_GAS)
+                .storage_unregister_callback(
+                    account_id,
+                    settled_accounts,
+                    U128(account_storage.deposit)
+                )
+            );
+
+        true
+    }
+
+    #[private]
+    pub fn storage_unregister_callback(&mut self, account_id: AccountId, settled_accounts: Vec<(PoolId, Account)>, storage_deposit: U128) -> bool {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        let principal: Balance = settled_accounts.iter().map(|(_, account)| account.stake_balance + account.unstake_balance).sum();
+        self.in_flight_outflow -= self.internal_ft_principal(principal);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                for (pool_id, settled_account) in settled_accounts {
+                    let mut pool: Pool = self.internal_get_pool(pool_id);
+                    pool.total_paid_reward_balance += settled_account.pre_reward + settled_account.referral_reward;
+                    self.pools.replace(pool_id as u64, &pool);
+
+                    self.internal_accrue_referral_reward(pool_id, &account_id, settled_account.referrer_id, settled_account.pre_reward);
+                }
+
+                if storage_deposit.0 > 0 {
+                    Promise::new(account_id).transfer(storage_deposit.0);
+                }
+                true
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data: đăng ký lại account, token chưa trả được chuyển thành unstake balance rút được ngay
+                match self.storage_accounts.get(&account_id) {
+                    Some(mut account_storage) => {
+                        account_storage.deposit += storage_deposit.0;
+                        self.storage_accounts.insert(&account_id, &account_storage);
+                    },
+                    None => self.internal_register_storage(&account_id, storage_deposit.0),
+                }
+
+                let mut ft_amount: Balance = 0;
+                for (pool_id, settled_account) in settled_accounts {
+                    if !self.accounts.contains_key(&(pool_id, account_id.clone())) {
+                        self.internal_register_account(pool_id, account_id.clone());
+                    }
+
+                    let mut account: Account = self.internal_get_account(pool_id, &account_id);
+                    account.pre_reward += settled_account.pre_reward;
+                    account.referral_reward += settled_account.referral_reward;
+                    account.referrer_id = account.referrer_id.or(settled_account.referrer_id);
+                    ft_amount += settled_account.pre_reward + settled_account.referral_reward;
+
+                    if self.staking_mode == StakingMode::FungibleToken {
+                        let principal: Balance = settled_account.stake_balance + settled_account.unstake_balance;
+                        account.unstake_balance += principal;
+                        ft_amount += principal;
+                        account.unstake_start_timestamp = env::block_timestamp();
+                        account.unstake_available_epoch = account.unstake_available_epoch.max(env::epoch_height());
+
+                        let mut pool: Pool = self.internal_get_pool(pool_id);
+                        pool.total_unstake_balance += principal;
+                        self.pools.replace(pool_id as u64, &pool);
+                    }
+
+                    self.internal_save_account(pool_id, &account_id, account);
+                }
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("storage_unregister_callback"),
+                    account_id,
+                    pool_id: None,
+                    amount: U128(ft_amount),
+                }]).emit();
+                false
+            },
+        }
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(env::storage_byte_cost() * Balance::from(self.account_storage_usage)),
+            max: None,
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        match self.storage_accounts.get(&account_id) {
+            Some(account_storage) => Some(account_storage.to_storage_balance()),
+            // account của bản single pool chưa migrate coi như đã đăng ký đủ storage
+            None => self.internal_get_old_account(&account_id)
+                .map(|_| StorageBalance { total: self.storage_balance_bounds().min, available: U128(0) }),
+        }
+    }
+}
+
+impl StakingContract {
+
+    // Đo số byte cần cho 1 account: record storage + account ở 1 pool, dùng account id dài nhất
+    pub(crate) fn internal_measure_account_storage_usage(&mut self) {
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        let tmp_account_id: AccountId = AccountId::new_unchecked("a".repeat(MAX_ACCOUNT_ID_LENGTH));
+
+        let mut pool: Pool = self.internal_get_pool(0);
+
+        self.storage_accounts.insert(&tmp_account_id, &AccountStorage { deposit: 0, storage_usage: 0 });
+        // account có người giới thiệu với account id dài nhất
+        let mut tmp_account: Account = Account::new(0);
+        tmp_account.referrer_id = Some(tmp_account_id.clone());
+        tmp_account.last_harvest_block = Some(0);
+        self.accounts.insert(&(0, tmp_account_id.clone()), &UpgradableAccount::from(tmp_account));
+        pool.account_ids.insert(&tmp_account_id);
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+
+        pool.account_ids.remove(&tmp_account_id);
+        self.accounts.remove(&(0, tmp_account_id.clone()));
+        self.storage_accounts.remove(&tmp_account_id);
+    }
+
+    pub(crate) fn internal_register_storage(&mut self, account_id: &AccountId, deposit: Balance) {
+        let initial_storage_usage: StorageUsage = env::storage_usage();
+        self.storage_accounts.insert(account_id, &AccountStorage { deposit, storage_usage: 0 });
+
+        self.internal_update_storage_usage(account_id, initial_storage_usage);
+    }
+
+    // Cộng/trừ phần storage thay đổi kể từ initial_storage_usage vào account, NEAR deposit phải đủ trả cho storage
+    pub(crate) fn internal_update_storage_usage(&mut self, account_id: &AccountId, initial_storage_usage: StorageUsage) {
+        let mut account_storage: AccountStorage = self.storage_accounts.get(account_id).expect("ERR_NOT_REGISTERED");
+        let current_storage_usage: StorageUsage = env::storage_usage();
+
+        if current_storage_usage >= initial_storage_usage {
+            account_storage.storage_usage += current_storage_usage - initial_storage_usage;
+        } else {
+            account_storage.storage_usage -= initial_storage_usage - current_storage_usage;
+        }
+
+        assert!(account_storage.deposit >= account_storage.storage_cost(), "ERR_INSUFFICIENT_STORAGE_DEPOSIT");
+
+        self.storage_accounts.insert(account_id, &account_storage);
+    }
+}
@@ -0,0 +1,171 @@
+use near_sdk::Timestamp;
+use near_sdk::json_types::U64;
+
+use crate::*;
+
+// thời gian chờ mặc định trước khi admin action được thực thi: 1 ngày (nano giây)
+pub const DEFAULT_TIMELOCK_DELAY: Timestamp = 86_400_000_000_000;
+
+// Các thay đổi admin phải qua timelock để user kịp phản ứng, pause / resume vẫn thực thi ngay
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AdminAction {
+    Config { pool_id: PoolId, config: ConfigForReward },
+    UnbondingPeriod { pool_id: PoolId, unbonding_period: EpochHeight },
+    TimelockDelay { delay: U64 },
+    PerformanceFee { fee_bps: u32 },
+    StakeLimits { pool_id: PoolId, limits: StakeLimits },
+    ReferralRate { pool_id: PoolId, referral_bps: u32 },
+    Loyalty { pool_id: PoolId, loyalty: LoyaltyConfig },
+    Vesting { pool_id: PoolId, vesting: Option<VestingConfig> },
+    HarvestLimits { pool_id: PoolId, limits: HarvestLimits },
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ScheduledAction {
+    pub action: AdminAction,
+    pub eta: Timestamp, // thời điểm sớm nhất được execute
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ScheduledActionJson {
+    pub action_id: U64,
+    pub action: AdminAction,
+    pub eta: U64,
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    pub fn schedule_action(&mut self, action: AdminAction) -> U64 {
+        self.assert_role(Role::ConfigManager);
+        self.internal_validate_action(&action);
+
+        let action_id: u64 = self.next_action_id;
+        let eta: Timestamp = env::block_timestamp() + self.timelock_delay;
+
+        self.scheduled_actions.insert(&action_id, &ScheduledAction { action: action.clone(), eta });
+        self.next_action_id += 1;
+
+        EventLogVariant::ScheduleAction(vec![ActionLog { action_id: U64(action_id), action, eta: U64(eta) }]).emit();
+
+        U64(action_id)
+    }
+
+    pub fn execute_action(&mut self, action_id: U64) {
+        self.assert_role(Role::ConfigManager);
+        let scheduled_action: ScheduledAction = self.scheduled_actions.get(&action_id.0).expect("ERR_ACTION_NOT_FOUND");
+        assert!(env::block_timestamp() >= scheduled_action.eta, "ERR_TIMELOCK_NOT_EXPIRED");
+
+        self.scheduled_actions.remove(&action_id.0);
+
+        match scheduled_action.action.clone() {
+            AdminAction::Config { pool_id, config } => self.internal_update_config(pool_id, config),
+            AdminAction::UnbondingPeriod { pool_id, unbonding_period } => {
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.unbonding_period = unbonding_period;
+                self.pools.replace(pool_id as u64, &pool);
+            },
+            AdminAction::TimelockDelay { delay } => self.timelock_delay = delay.0,
+            AdminAction::PerformanceFee { fee_bps } => self.fee_bps = fee_bps,
+            AdminAction::StakeLimits { pool_id, limits } => {
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.limits = limits;
+                self.pools.replace(pool_id as u64, &pool);
+            },
+            AdminAction::ReferralRate { pool_id, referral_bps } => {
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.referral_bps = referral_bps;
+                self.pools.replace(pool_id as u64, &pool);
+            },
+            AdminAction::Loyalty { pool_id, loyalty } => self.internal_update_loyalty(pool_id, loyalty),
+            AdminAction::Vesting { pool_id, vesting } => {
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.vesting = vesting;
+                self.pools.replace(pool_id as u64, &pool);
+            },
+            AdminAction::HarvestLimits { pool_id, limits } => {
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                pool.harvest_limits = limits;
+                self.pools.replace(pool_id as u64, &pool);
+            },
+        }
+
+        EventLogVariant::ExecuteAction(vec![ActionLog {
+            action_id,
+            action: scheduled_action.action,
+            eta: U64(scheduled_action.eta),
+        }]).emit();
+    }
+
+    pub fn cancel_action(&mut self, action_id: U64) {
+        self.assert_role(Role::ConfigManager);
+        let scheduled_action: ScheduledAction = self.scheduled_actions.remove(&action_id.0).expect("ERR_ACTION_NOT_FOUND");
+
+        EventLogVariant::CancelAction(vec![ActionLog {
+            action_id,
+            action: scheduled_action.action,
+            eta: U64(scheduled_action.eta),
+        }]).emit();
+    }
+
+    pub fn get_scheduled_actions(&self) -> Vec<ScheduledActionJson> {
+        self.scheduled_actions.iter()
+            .map(|(action_id, scheduled_action)| ScheduledActionJson {
+                action_id: U64(action_id),
+                action: scheduled_action.action,
+                eta: U64(scheduled_action.eta),
+            })
+            .collect()
+    }
+
+    pub fn get_timelock_delay(&self) -> U64 {
+        U64(self.timelock_delay)
+    }
+}
+
+impl StakingContract {
+
+    // kiểm tra lúc schedule để action sai không phải chờ hết timelock mới biết
+    fn internal_validate_action(&self, action: &AdminAction) {
+        match action {
+            AdminAction::Config { pool_id, config } => {
+                self.internal_get_pool(*pool_id);
+                assert!(config.reward_denumerator > 0, "ERR_INVALID_CONFIG");
+            },
+            AdminAction::UnbondingPeriod { pool_id, .. } => {
+                self.internal_get_pool(*pool_id);
+            },
+            AdminAction::TimelockDelay { .. } => {},
+            AdminAction::PerformanceFee { fee_bps } => {
+                assert!(*fee_bps <= MAX_FEE_BPS, "ERR_INVALID_FEE");
+            },
+            AdminAction::StakeLimits { pool_id, limits } => {
+                self.internal_get_pool(*pool_id);
+                assert!(limits.deposit_fee_bps < MAX_FEE_BPS, "ERR_INVALID_FEE");
+                // treasury chỉ giữ ft token nên không thu phí stake bằng NEAR
+                assert!(limits.deposit_fee_bps == 0 || self.staking_mode == StakingMode::FungibleToken, "ERR_INVALID_FEE");
+            },
+            AdminAction::ReferralRate { pool_id, referral_bps } => {
+                self.internal_get_pool(*pool_id);
+                assert!(*referral_bps <= MAX_FEE_BPS, "ERR_INVALID_REFERRAL_RATE");
+            },
+            AdminAction::Loyalty { pool_id, loyalty } => {
+                self.internal_get_pool(*pool_id);
+                assert!(loyalty.max_multiplier_bps >= LOYALTY_BASE_BPS, "ERR_INVALID_LOYALTY_CONFIG");
+            },
+            AdminAction::Vesting { pool_id, vesting } => {
+                self.internal_get_pool(*pool_id);
+                if let Some(vesting) = vesting {
+                    assert!(vesting.duration_blocks > 0 && vesting.cliff_blocks <= vesting.duration_blocks, "ERR_INVALID_VESTING_CONFIG");
+                    assert!(vesting.early_claim_penalty_bps <= MAX_FEE_BPS, "ERR_INVALID_VESTING_CONFIG");
+                }
+            },
+            AdminAction::HarvestLimits { pool_id, .. } => {
+                self.internal_get_pool(*pool_id);
+            },
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use near_sdk::{Gas, PromiseResult};
+
+use crate::*;
+
+// 10000 basis point = 100%
+pub const MAX_FEE_BPS: u32 = 10_000;
+pub const CLAIM_TREASURY_CALLBACK_GAS: Gas = Gas(10_000_000_000_000);
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TreasuryJson {
+    pub fee_bps: u32,
+    pub treasury_balance: U128,
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Rút phí trong treasury về account gọi, amount để trống thì rút hết
+    #[payable]
+    pub fn claim_treasury(&mut self, amount: Option<U128>) -> Promise {
+        assert_one_yocto();
+        self.assert_role(Role::Treasurer);
+        let account_id: AccountId = env::predecessor_account_id();
+
+        let amount: Balance = amount.map(|amount| amount.0).unwrap_or(self.treasury_balance);
+        assert!(amount > 0, "ERR_AMOUNT_EQUAL_ZERO");
+        assert!(amount <= self.treasury_balance, "ERR_AMOUNT_GREATER_THAN_TREASURY");
+
+        self.treasury_balance -= amount;
+        self.in_flight_outflow += amount;
+
+        ext_ft_contract::ext(self.ft_contract_id.clone())
+            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+            .with_static_gas(FT_TRANSFER_GAS)
+            .ft_transfer(
+                account_id.clone(),
+                U128(amount),
+                Some(String::from("Staking contract claim treasury"))
+            ).then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(CLAIM_TREASURY_CALLBACK_GAS)
+                .claim_treasury_callback(account_id, U128(amount))
+            )
+    }
+
+    #[private]
+    pub fn claim_treasury_callback(&mut self, account_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        self.in_flight_outflow -= amount.0;
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                EventLogVariant::ClaimTreasury(vec![TreasuryLog { account_id, amount }]).emit();
+                amount
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data
+                self.treasury_balance += amount.0;
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("claim_treasury_callback"),
+                    account_id,
+                    pool_id: None,
+                    amount,
+                }]).emit();
+                U128(0)
+            },
+        }
+    }
+
+    pub fn get_treasury(&self) -> TreasuryJson {
+        TreasuryJson {
+            fee_bps: self.fee_bps,
+            treasury_balance: U128(self.treasury_balance),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use near_sdk::{Gas, PromiseResult};
+
+use crate::*;
+
+// độ chính xác của penalty_per_share
+pub const PENALTY_PRECISION: u128 = 1_000_000_000_000_000_000_000_000;
+pub const CLAIM_VESTED_CALLBACK_GAS: Gas = Gas(10_000_000_000_000);
+
+// số tranche chưa mở khoá hết tối đa của 1 account, storage của mỗi tranche trừ vào storage deposit
+pub const MAX_VESTING_TRANCHES: usize = 10;
+
+// Reward của 1 lần harvest, vest theo lịch riêng bắt đầu từ block harvest
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingTranche {
+    pub total_balance: Balance,
+    pub released_balance: Balance, // phần đã mở khoá
+    pub start_block: BlockHeight,
+    pub config: VestingConfig, // giữ config lúc harvest để đổi config không ảnh hưởng reward đã vest
+}
+
+impl VestingTranche {
+    pub fn vested_balance(&self, block_index: BlockHeight) -> Balance {
+        let elapsed: BlockHeight = block_index.saturating_sub(self.start_block);
+        if elapsed < self.config.cliff_blocks {
+            return 0;
+        }
+        if elapsed >= self.config.duration_blocks {
+            return self.total_balance;
+        }
+
+        self.total_balance * elapsed as u128 / self.config.duration_blocks as u128
+    }
+
+    pub fn locked_balance(&self) -> Balance {
+        self.total_balance - self.released_balance
+    }
+}
+
+// Lịch vesting của account, mỗi lần harvest thêm 1 tranche nên reward đã vest trước đó không bị tính lại từ đầu
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingSchedule {
+    pub tranches: Vec<VestingTranche>, // các tranche còn phần chưa mở khoá
+    pub claimable_balance: Balance, // đã mở khoá nhưng chưa claim, kể cả của các tranche đã mở khoá hết
+}
+
+impl VestingSchedule {
+    pub fn locked_balance(&self) -> Balance {
+        self.tranches.iter().map(|tranche| tranche.locked_balance()).sum()
+    }
+
+    // phí claim sớm tính theo config của từng tranche
+    pub fn early_claim_penalty(&self) -> Balance {
+        self.tranches.iter()
+            .map(|tranche| tranche.locked_balance() * tranche.config.early_claim_penalty_bps as u128 / MAX_FEE_BPS as u128)
+            .sum()
+    }
+
+    // tổng reward contract còn phải trả cho account
+    pub fn balance(&self) -> Balance {
+        self.locked_balance() + self.claimable_balance
+    }
+
+    pub fn release(&mut self, block_index: BlockHeight) {
+        for tranche in self.tranches.iter_mut() {
+            let vested_balance: Balance = tranche.vested_balance(block_index);
+
+            self.claimable_balance += vested_balance - tranche.released_balance;
+            tranche.released_balance = vested_balance;
+        }
+
+        self.tranches.retain(|tranche| tranche.locked_balance() > 0);
+    }
+
+    pub fn add(&mut self, amount: Balance, config: VestingConfig, block_index: BlockHeight) {
+        self.release(block_index);
+        assert!(self.tranches.len() < MAX_VESTING_TRANCHES, "ERR_TOO_MANY_VESTING_TRANCHES");
+
+        self.tranches.push(VestingTranche {
+            total_balance: amount,
+            released_balance: 0,
+            start_block: block_index,
+            config,
+        });
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct VestingJson {
+    pub pool_id: PoolId,
+    pub account_id: AccountId,
+    pub locked_balance: U128,
+    pub claimable_balance: U128,
+    pub early_claim_penalty: U128, // phí nếu claim_vested với early = true lúc này
+    pub tranches: Vec<VestingTranche>,
+}
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Rút phần reward đã mở khoá, early = true thì rút luôn phần còn khoá và chịu phí chia cho các staker của pool
+    #[payable]
+    pub fn claim_vested(&mut self, pool_id: PoolId, early: Option<bool>) -> Promise {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+
+        account.vesting.release(env::block_height());
+        let mut amount: Balance = account.vesting.claimable_balance;
+        let mut penalty: Balance = 0;
+
+        if early.unwrap_or(false) {
+            penalty = account.vesting.early_claim_penalty();
+            amount += account.vesting.locked_balance() - penalty;
+            account.vesting = VestingSchedule::default();
+        } else {
+            account.vesting.claimable_balance = 0;
+        }
+
+        assert!(amount > 0, "ERR_AMOUNT_EQUAL_ZERO");
+        account.pending_transfer_count += 1;
+
+        pool.total_vesting_balance -= amount + penalty;
+        self.internal_distribute_penalty(&mut pool, penalty);
+
+        self.internal_save_account(pool_id, &account_id, account);
+        self.pools.replace(pool_id as u64, &pool);
+        self.in_flight_outflow += amount;
+
+        ext_ft_contract::ext(self.ft_contract_id.clone())
+            .with_attached_deposit(DEPOSIT_ONE_YOCTOR)
+            .with_static_gas(FT_TRANSFER_GAS)
+            .ft_transfer(
+                account_id.clone(),
+                U128(amount),
+                Some(String::from("Staking contract claim vested"))
+            ).then(
+                ext_self::ext(env::current_account_id())
+                .with_attached_deposit(NO_DEPOSIT)
+                .with_static_gas(CLAIM_VESTED_CALLBACK_GAS)
+                .claim_vested_callback(pool_id, account_id, U128(amount))
+            )
+    }
+
+    #[private]
+    pub fn claim_vested_callback(&mut self, pool_id: PoolId, account_id: AccountId, amount: U128) -> U128 {
+        assert_eq!(env::promise_results_count(), 1, "ERR_TOO_MANY_RESULTS");
+        self.in_flight_outflow -= amount.0;
+        self.internal_finish_transfer(pool_id, &account_id);
+        match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(_value) => {
+                EventLogVariant::ClaimVested(vec![AccountLog { pool_id, account_id, amount }]).emit();
+                amount
+            },
+            PromiseResult::Failed => {
+                // Handle rollback data: phí claim sớm đã chia cho staker nên chỉ trả lại phần đã rút
+                let mut pool: Pool = self.internal_get_pool(pool_id);
+                let mut account: Account = self.internal_get_account(pool_id, &account_id);
+
+                account.vesting.claimable_balance += amount.0;
+                pool.total_vesting_balance += amount.0;
+
+                self.internal_save_account(pool_id, &account_id, account);
+                self.pools.replace(pool_id as u64, &pool);
+
+                EventLogVariant::CallbackFailed(vec![CallbackFailedLog {
+                    callback: String::from("claim_vested_callback"),
+                    account_id,
+                    pool_id: Some(pool_id),
+                    amount,
+                }]).emit();
+                U128(0)
+            },
+        }
+    }
+
+    pub fn get_vesting(&self, pool_id: PoolId, account_id: AccountId) -> VestingJson {
+        let mut vesting: VestingSchedule = self.internal_get_account(pool_id, &account_id).vesting;
+        vesting.release(env::block_height());
+        let early_claim_penalty: Balance = vesting.early_claim_penalty();
+
+        VestingJson {
+            pool_id,
+            account_id,
+            locked_balance: U128(vesting.locked_balance()),
+            claimable_balance: U128(vesting.claimable_balance),
+            early_claim_penalty: U128(early_claim_penalty),
+            tranches: vesting.tranches,
+        }
+    }
+}
+
+impl StakingContract {
+
+    // Harvest khi pool bật vesting: reward (sau phí) được chuyển vào lịch vesting thay vì transfer ngay
+    pub(crate) fn internal_vest_reward(&mut self, pool_id: PoolId, account_id: AccountId, config: VestingConfig) -> Balance {
+        let mut pool: Pool = self.internal_get_pool(pool_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+
+        self.internal_settle_account_reward(&mut pool, &mut account);
+        let own_reward: Balance = account.pre_reward;
+        let current_reward: Balance = own_reward + account.referral_reward;
+        let fee: Balance = self.internal_calculate_fee(current_reward);
+        let amount: Balance = current_reward - fee;
+
+        assert!(amount > 0, "ERR_REWARD_EQUAL_ZERO");
+
+        let referrer_id: Option<AccountId> = account.referrer_id.clone();
+        account.pre_reward = 0;
+        account.referral_reward = 0;
+        account.vesting.add(amount, config, env::block_height());
+
+        self.internal_save_account(pool_id, &account_id, account);
+
+        // reward đã vest tính là đã trả, phần chưa claim được theo dõi riêng trong total_vesting_balance
+        pool.total_paid_reward_balance += current_reward;
+        pool.total_vesting_balance += amount;
+        self.pools.replace(pool_id as u64, &pool);
+        self.treasury_balance += fee;
+        self.internal_accrue_referral_reward(pool_id, &account_id, referrer_id, own_reward);
+
+        EventLogVariant::Vest(vec![AccountLog { pool_id, account_id, amount: U128(amount) }]).emit();
+
+        amount
+    }
+
+    // Chia phí claim sớm cho các staker theo stake balance, pool không còn stake thì cộng vào quỹ reward
+    pub(crate) fn internal_distribute_penalty(&self, pool: &mut Pool, penalty: Balance) {
+        if penalty == 0 {
+            return;
+        }
+
+        let penalty_per_share: Option<u128> = if pool.total_stake_balance > 0 {
+            let increase: U256 = U256::from(penalty) * U256::from(PENALTY_PRECISION) / U256::from(pool.total_stake_balance);
+            u128::try_from(increase).ok().and_then(|increase| pool.penalty_per_share.checked_add(increase))
+        } else {
+            None
+        };
+
+        match penalty_per_share {
+            Some(penalty_per_share) => {
+                pool.penalty_per_share = penalty_per_share;
+                pool.total_penalty_reward_balance += penalty;
+            },
+            None => pool.total_funded_reward_balance += penalty,
+        }
+    }
+
+    pub(crate) fn internal_calculate_penalty_share(&self, pool: &Pool, account: &Account) -> Balance {
+        let share: U256 = U256::from(account.stake_balance) * U256::from(pool.penalty_per_share - account.penalty_per_share_paid) / U256::from(PENALTY_PRECISION);
+
+        share.as_u128()
+    }
+}
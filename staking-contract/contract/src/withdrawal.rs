@@ -0,0 +1,83 @@
+use crate::*;
+
+// số account tối đa mỗi lần process_withdrawals, mỗi account cần 1 transfer và 1 callback
+pub const MAX_WITHDRAWALS_PER_CALL: usize = 10;
+
+#[near_bindgen]
+impl StakingContract {
+
+    // Đăng ký rút tự động: sau khi hết unbonding, keeper bất kỳ gọi process_withdrawals để trả unstake balance về account
+    #[payable]
+    pub fn schedule_withdraw(&mut self, pool_id: PoolId) {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        self.internal_migrate_on_access(pool_id, &account_id);
+
+        self.internal_schedule_withdraw(pool_id, account_id);
+    }
+
+    // Unstake và đăng ký rút tự động trong cùng 1 lần gọi
+    #[payable]
+    pub fn unstake_and_schedule_withdraw(&mut self, pool_id: PoolId, amount: U128) {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+
+        self.internal_unstake(pool_id, account_id.clone(), amount.0);
+        self.internal_schedule_withdraw(pool_id, account_id);
+    }
+
+    #[payable]
+    pub fn cancel_scheduled_withdraw(&mut self, pool_id: PoolId) {
+        assert_one_yocto();
+        let account_id: AccountId = env::predecessor_account_id();
+        self.internal_migrate_on_access(pool_id, &account_id);
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+        assert!(account.withdraw_scheduled, "ERR_WITHDRAW_NOT_SCHEDULED");
+
+        let amount: U128 = U128(account.unstake_balance);
+        account.withdraw_scheduled = false;
+        self.internal_save_account(pool_id, &account_id, account);
+
+        EventLogVariant::CancelScheduledWithdraw(vec![AccountLog { pool_id, account_id, amount }]).emit();
+    }
+
+    // Ai cũng gọi được, account chưa đăng ký hoặc chưa hết unbonding được bỏ qua để không làm lỗi cả batch.
+    // Mỗi account có transfer và callback riêng nên transfer lỗi chỉ rollback account đó
+    pub fn process_withdrawals(&mut self, pool_id: PoolId, account_ids: Vec<AccountId>) -> Promise {
+        assert!(account_ids.len() <= MAX_WITHDRAWALS_PER_CALL, "ERR_TOO_MANY_ACCOUNTS");
+
+        let mut withdraw_promise: Option<Promise> = None;
+        for account_id in account_ids {
+            let ready: bool = self.accounts.get(&(pool_id, account_id.clone()))
+                .map(Account::from)
+                .is_some_and(|account| account.withdraw_scheduled && account.unstake_balance > 0 && account.unstake_available_epoch <= env::epoch_height());
+            if !ready {
+                continue;
+            }
+
+            let old_account: Account = self.internal_withdraw(pool_id, account_id.clone());
+            let promise: Promise = self.internal_withdraw_transfer(pool_id, account_id, old_account);
+
+            withdraw_promise = Some(match withdraw_promise {
+                Some(withdraw_promise) => withdraw_promise.and(promise),
+                None => promise,
+            });
+        }
+
+        withdraw_promise.expect("ERR_NO_WITHDRAWAL_READY")
+    }
+}
+
+impl StakingContract {
+
+    pub(crate) fn internal_schedule_withdraw(&mut self, pool_id: PoolId, account_id: AccountId) {
+        let mut account: Account = self.internal_get_account(pool_id, &account_id);
+        assert!(account.unstake_balance > 0, "ERR_UNSTAKE_BALANCE_IS_ZERO");
+
+        let amount: U128 = U128(account.unstake_balance);
+        account.withdraw_scheduled = true;
+        self.internal_save_account(pool_id, &account_id, account);
+
+        EventLogVariant::ScheduleWithdraw(vec![AccountLog { pool_id, account_id, amount }]).emit();
+    }
+}
@@ -140,6 +140,7 @@ fn deposit_and_stake_test() {
         staking_contract.account_id(), 
         "get_account_info", 
         &json!({
+            "pool_id": 0,
             "account_id": alice.account_id()
         }).to_string().as_bytes()
     ).unwrap_json();
@@ -174,7 +175,7 @@ fn deposit_and_stake_error_storage_test() {
     // assert error type
     if let ExecutionStatus::Failure(error) = &outcome.promise_errors().remove(0).unwrap().outcome().status {
         println!("Error: {}", error.to_string());
-        assert!(error.to_string().contains("ERR_NOT_FOUND_ACCOUNT"));
+        assert!(error.to_string().contains("ERR_ACCOUNT_NOT_FOUND"));
     } else {
         unreachable!();
     }